@@ -0,0 +1,151 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use text_editor::backend::{Action, Backend};
+
+const LINES: usize = 200_000;
+
+fn large_document() -> String {
+    (0..LINES)
+        .map(|i| format!("{i:>8}: the quick brown fox jumps over the lazy dog\n"))
+        .collect()
+}
+
+/// The baseline the rope is measured against: the document as a `Vec` of
+/// rows, with the cursor as a row and a char column
+struct VecBackend {
+    lines: Vec<String>,
+    row: usize,
+    column: usize,
+}
+
+impl VecBackend {
+    fn new(text: &str) -> Self {
+        Self {
+            lines: text.split('\n').map(str::to_string).collect(),
+            row: 0,
+            column: 0,
+        }
+    }
+
+    fn byte_index(&self) -> usize {
+        let line = &self.lines[self.row];
+        line.char_indices()
+            .nth(self.column)
+            .map_or(line.len(), |(i, _)| i)
+    }
+
+    fn insert(&mut self, c: char) {
+        let index = self.byte_index();
+        self.lines[self.row].insert(index, c);
+        self.column += 1;
+    }
+
+    fn enter(&mut self) {
+        let index = self.byte_index();
+        let rest = self.lines[self.row].split_off(index);
+        self.lines.insert(self.row + 1, rest);
+        self.row += 1;
+        self.column = 0;
+    }
+
+    fn backspace(&mut self) {
+        if self.column > 0 {
+            self.column -= 1;
+            let index = self.byte_index();
+            self.lines[self.row].remove(index);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.column = self.lines[self.row].chars().count();
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    fn line(&self, row: usize) -> &str {
+        &self.lines[row]
+    }
+}
+
+fn vec_at_middle(text: &str) -> VecBackend {
+    let mut backend = VecBackend::new(text);
+    backend.row = LINES / 2;
+    backend
+}
+
+/// A backend with the cursor parked in the middle of a large document
+fn backend_at_middle(text: &str) -> Backend {
+    let mut backend = Backend::from(text);
+    for _ in 0..LINES / 2 {
+        backend.action(Action::Down);
+    }
+    backend
+}
+
+fn edits(c: &mut Criterion) {
+    let text = large_document();
+    let backend = backend_at_middle(&text);
+
+    c.bench_function("insert in middle of large document", |b| {
+        b.iter_batched_ref(
+            || backend_at_middle(&text),
+            |backend| backend.action(Action::Insert(black_box('x'))),
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("enter in middle of large document", |b| {
+        b.iter_batched_ref(
+            || backend_at_middle(&text),
+            |backend| backend.action(Action::Enter),
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("backspace at line start in large document", |b| {
+        b.iter_batched_ref(
+            || {
+                let mut backend = backend_at_middle(&text);
+                backend.action(Action::Home);
+                backend
+            },
+            |backend| backend.action(Action::Backspace),
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("line lookup in large document", |b| {
+        b.iter(|| backend.line(black_box(LINES / 2)).len())
+    });
+}
+
+fn vec_edits(c: &mut Criterion) {
+    let text = large_document();
+    let backend = vec_at_middle(&text);
+
+    c.bench_function("vec baseline: insert in middle of large document", |b| {
+        b.iter_batched_ref(
+            || vec_at_middle(&text),
+            |backend| backend.insert(black_box('x')),
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("vec baseline: enter in middle of large document", |b| {
+        b.iter_batched_ref(
+            || vec_at_middle(&text),
+            |backend| backend.enter(),
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function(
+        "vec baseline: backspace at line start in large document",
+        |b| {
+            b.iter_batched_ref(
+                || vec_at_middle(&text),
+                |backend| backend.backspace(),
+                BatchSize::LargeInput,
+            )
+        },
+    );
+    c.bench_function("vec baseline: line lookup in large document", |b| {
+        b.iter(|| backend.line(black_box(LINES / 2)).len())
+    });
+}
+
+criterion_group!(benches, edits, vec_edits);
+criterion_main!(benches);
@@ -0,0 +1,459 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use iced::futures::{channel::mpsc, SinkExt, StreamExt};
+use iced::{
+    subscription, theme,
+    widget::{button, container, scrollable, text, text_input, Column, Row},
+    Color, Element, Length, Padding, Subscription,
+};
+use notify::{RecursiveMode, Watcher};
+
+use crate::Message;
+
+/// How far each level of the tree is indented
+const INDENT: f32 = 15.0;
+
+/// A file or directory in the tree
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    is_dir: bool,
+    /// Whether git ignores it, which dims it
+    ignored: bool,
+}
+
+/// A change to the project's files that's waiting to be confirmed
+#[derive(Debug)]
+enum Operation {
+    /// Create a file called `name` in `dir`
+    Create {
+        dir: PathBuf,
+        name: String,
+    },
+    Rename {
+        path: PathBuf,
+        name: String,
+    },
+    Delete(PathBuf),
+}
+
+/// What confirming an operation did that open buffers need to know about
+#[derive(Debug)]
+pub enum Change {
+    Created(PathBuf),
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// A file, or a directory and everything in it
+    Deleted(PathBuf),
+}
+
+/// The sidebar showing the project's files. Directories are only read once
+/// they're expanded.
+#[derive(Debug)]
+pub struct FileTree {
+    root: PathBuf,
+    // What's in each expanded directory
+    expanded: HashMap<PathBuf, Vec<Entry>>,
+    selected: Option<PathBuf>,
+    operation: Option<Operation>,
+    // Why the last operation failed
+    error: Option<String>,
+}
+
+/// What's in `dir`, directories first. Everything in an ignored directory
+/// is ignored too.
+fn read(dir: &Path, ignored: bool) -> Vec<Entry> {
+    // The walk leaves out what git ignores, so anything it doesn't find is
+    // ignored
+    let kept: HashSet<PathBuf> = ignore::WalkBuilder::new(dir)
+        .max_depth(Some(1))
+        .hidden(false)
+        .build()
+        .filter_map(Result::ok)
+        .map(ignore::DirEntry::into_path)
+        .collect();
+    let Ok(read) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<Entry> = read
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name() != ".git")
+        .map(|entry| {
+            let path = entry.path();
+            Entry {
+                is_dir: path.is_dir(),
+                ignored: ignored || !kept.contains(&path),
+                path,
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.path.cmp(&b.path)));
+    entries
+}
+
+/// An icon for the kind of file at `path`
+fn icon(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    match extension.unwrap_or_default() {
+        "rs" => "🦀",
+        "md" | "txt" => "📝",
+        "toml" | "json" | "yaml" | "yml" | "lock" => "⚙",
+        "js" | "ts" | "py" | "sh" | "c" | "h" | "cpp" | "go" => "📜",
+        "png" | "jpg" | "jpeg" | "gif" | "svg" => "🖼",
+        _ => "📄",
+    }
+}
+
+pub fn input_id() -> text_input::Id {
+    text_input::Id::new("file-name")
+}
+
+/// `name` if it's the name of a file, rather than a path
+fn valid(name: &str) -> io::Result<&str> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(name),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("\"{name}\" isn't a file name"),
+        )),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Produce an event whenever something in one of `dirs` changes on disk
+pub fn watch(dirs: Vec<PathBuf>) -> Subscription<()> {
+    struct Watch;
+
+    // Watching other directories starts over with a new watcher
+    let id = (std::any::TypeId::of::<Watch>(), dirs.clone());
+    subscription::channel(id, 1, |mut output| async move {
+        let (mut sender, mut changes) = mpsc::channel(1);
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if event.is_ok_and(|event| !event.kind.is_access()) {
+                // A change is already waiting to be reported if this fails
+                let _ = sender.try_send(());
+            }
+        })
+        .map(|mut watcher| {
+            for dir in &dirs {
+                // A directory that's gone is dropped from the tree anyway
+                let _ = watcher.watch(dir, RecursiveMode::NonRecursive);
+            }
+            watcher
+        });
+        loop {
+            match &watcher {
+                Ok(_) => {
+                    changes.next().await;
+                    let _ = output.send(()).await;
+                }
+                // Without a watcher, there's nothing to report
+                Err(_) => iced::futures::future::pending().await,
+            }
+        }
+    })
+}
+
+impl FileTree {
+    /// Show the files in `root`, which is expanded to start with
+    pub fn new(root: PathBuf) -> Self {
+        let entries = read(&root, false);
+        Self {
+            expanded: HashMap::from([(root.clone(), entries)]),
+            root,
+            selected: None,
+            operation: None,
+            error: None,
+        }
+    }
+
+    /// The directories being shown, which are the ones to watch
+    pub fn expanded(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self.expanded.keys().cloned().collect();
+        dirs.sort();
+        dirs
+    }
+
+    /// `path` relative to where the editor was started, like the paths it's
+    /// given
+    fn relative(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.root).unwrap_or(path).to_path_buf()
+    }
+
+    fn entry(&self, path: &Path) -> Option<&Entry> {
+        self.expanded
+            .get(path.parent()?)?
+            .iter()
+            .find(|entry| entry.path == path)
+    }
+
+    fn expand(&mut self, dir: PathBuf) {
+        let ignored = self.entry(&dir).is_some_and(|entry| entry.ignored);
+        let entries = read(&dir, ignored);
+        self.expanded.insert(dir, entries);
+    }
+
+    /// Select `path`, expanding or collapsing it if it's a directory.
+    /// Returns the file to open if it's a file.
+    pub fn click(&mut self, path: PathBuf) -> Option<PathBuf> {
+        self.selected = Some(path.clone());
+        if !path.is_dir() {
+            return Some(self.relative(&path));
+        }
+        if self.expanded.contains_key(&path) {
+            self.expanded.retain(|dir, _| !dir.starts_with(&path));
+        } else {
+            self.expand(path);
+        }
+        None
+    }
+
+    /// Read the expanded directories again, after their files changed on disk
+    pub fn refresh(&mut self) {
+        // Parents come before their children, so whether they're ignored is
+        // known first
+        for dir in self.expanded() {
+            if dir == self.root || dir.is_dir() {
+                self.expand(dir);
+            } else {
+                self.expanded.remove(&dir);
+            }
+        }
+        if self.selected.as_ref().is_some_and(|path| !path.exists()) {
+            self.selected = None;
+        }
+    }
+
+    /// Where a new file goes: in the selected directory, or next to the
+    /// selected file
+    fn target_dir(&self) -> PathBuf {
+        match &self.selected {
+            Some(path) if path.is_dir() => path.clone(),
+            Some(path) => path
+                .parent()
+                .map_or_else(|| self.root.clone(), Path::to_path_buf),
+            None => self.root.clone(),
+        }
+    }
+
+    pub fn start_create(&mut self) {
+        self.operation = Some(Operation::Create {
+            dir: self.target_dir(),
+            name: String::new(),
+        });
+        self.error = None;
+    }
+
+    pub fn start_rename(&mut self) {
+        if let Some(path) = &self.selected {
+            self.operation = Some(Operation::Rename {
+                name: file_name(path),
+                path: path.clone(),
+            });
+            self.error = None;
+        }
+    }
+
+    pub fn start_delete(&mut self) {
+        if let Some(path) = &self.selected {
+            self.operation = Some(Operation::Delete(path.clone()));
+            self.error = None;
+        }
+    }
+
+    /// Set the name the file is created or renamed with
+    pub fn set_name(&mut self, name: String) {
+        if let Some(Operation::Create { name: old, .. } | Operation::Rename { name: old, .. }) =
+            &mut self.operation
+        {
+            *old = name;
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        self.operation = None;
+        self.error = None;
+    }
+
+    /// Carry out the operation waiting to be confirmed. If it fails, it's
+    /// left open with the reason shown.
+    pub fn confirm(&mut self) -> Option<Change> {
+        let operation = self.operation.take()?;
+        match self.apply(&operation) {
+            Ok(change) => {
+                self.error = None;
+                self.refresh();
+                change
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                self.operation = Some(operation);
+                None
+            }
+        }
+    }
+
+    fn apply(&mut self, operation: &Operation) -> io::Result<Option<Change>> {
+        match operation {
+            Operation::Create { dir, name } => {
+                let path = dir.join(valid(name)?);
+                // A file that's there already is never clobbered
+                fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)?;
+                if !self.expanded.contains_key(dir) {
+                    self.expand(dir.clone());
+                }
+                self.selected = Some(path.clone());
+                Ok(Some(Change::Created(self.relative(&path))))
+            }
+            Operation::Rename { path, name } => {
+                let to = path.with_file_name(valid(name)?);
+                if to == *path {
+                    return Ok(None);
+                }
+                if to.exists() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{name} already exists"),
+                    ));
+                }
+                fs::rename(path, &to)?;
+                self.selected = Some(to.clone());
+                Ok(Some(Change::Renamed {
+                    from: self.relative(path),
+                    to: self.relative(&to),
+                }))
+            }
+            Operation::Delete(path) => {
+                if path.is_dir() {
+                    fs::remove_dir_all(path)?;
+                } else {
+                    fs::remove_file(path)?;
+                }
+                Ok(Some(Change::Deleted(self.relative(path))))
+            }
+        }
+    }
+
+    fn operation_view(&self, operation: &Operation) -> Element<'_, Message> {
+        let (prompt, name, confirm) = match operation {
+            Operation::Create { dir, name } => (
+                format!("New file in {}", self.relative(dir).display()),
+                Some(name),
+                "Create",
+            ),
+            Operation::Rename { path, name } => {
+                (format!("Rename {}", file_name(path)), Some(name), "Rename")
+            }
+            Operation::Delete(path) if path.is_dir() => (
+                format!("Delete {} and everything in it?", file_name(path)),
+                None,
+                "Delete",
+            ),
+            Operation::Delete(path) => (format!("Delete {}?", file_name(path)), None, "Delete"),
+        };
+        let mut form = Column::new().spacing(5).push(text(prompt));
+        if let Some(name) = name {
+            form = form.push(
+                text_input("File name", name)
+                    .id(input_id())
+                    .on_input(Message::FileNameChanged)
+                    .on_submit(Message::ConfirmFileOperation),
+            );
+        }
+        if let Some(error) = &self.error {
+            form = form.push(text(error));
+        }
+        form.push(
+            Row::new()
+                .spacing(5)
+                .push(button(confirm).on_press(Message::ConfirmFileOperation))
+                .push(button("Cancel").on_press(Message::CancelFileOperation)),
+        )
+        .into()
+    }
+
+    /// Add a row for each entry in `dir`, and the entries of those that are
+    /// expanded under them. Ignored entries are shown in `dimmed`.
+    fn push_entries<'a>(
+        &'a self,
+        mut column: Column<'a, Message>,
+        dir: &Path,
+        depth: usize,
+        dimmed: Color,
+    ) -> Column<'a, Message> {
+        let Some(entries) = self.expanded.get(dir) else {
+            return column;
+        };
+        for entry in entries {
+            let is_expanded = self.expanded.contains_key(&entry.path);
+            let icon = match (entry.is_dir, is_expanded) {
+                (true, true) => "📂",
+                (true, false) => "📁",
+                (false, _) => icon(&entry.path),
+            };
+            let is_selected = self.selected.as_ref() == Some(&entry.path);
+            let mut name = text(file_name(&entry.path));
+            if entry.ignored && !is_selected {
+                name = name.style(dimmed);
+            }
+            let row = Row::new().spacing(5).push(text(icon)).push(name);
+            let style = if is_selected {
+                theme::Button::Primary
+            } else {
+                theme::Button::Text
+            };
+            column = column.push(
+                container(
+                    button(row)
+                        .width(Length::Fill)
+                        .padding([2, 5])
+                        .style(style)
+                        .on_press(Message::FileTreeClicked(entry.path.clone())),
+                )
+                .padding(Padding {
+                    left: depth as f32 * INDENT,
+                    ..Padding::ZERO
+                }),
+            );
+            if is_expanded {
+                column = self.push_entries(column, &entry.path, depth + 1, dimmed);
+            }
+        }
+        column
+    }
+
+    /// Git-ignored files are shown in `dimmed`
+    pub fn view(&self, dimmed: Color) -> Element<'_, Message> {
+        let mut rename = button("Rename");
+        let mut delete = button("Delete");
+        if self.selected.is_some() {
+            rename = rename.on_press(Message::RenameFile);
+            delete = delete.on_press(Message::DeleteFile);
+        }
+        let actions = Row::new()
+            .spacing(5)
+            .push(button("New").on_press(Message::NewFile))
+            .push(rename)
+            .push(delete);
+        let mut col = Column::new().spacing(10).push(actions);
+        if let Some(operation) = &self.operation {
+            col = col.push(self.operation_view(operation));
+        }
+        let tree = self.push_entries(Column::new(), &self.root, 0, dimmed);
+        col.push(scrollable(tree).height(Length::Fill)).into()
+    }
+}
@@ -0,0 +1,206 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use iced::{
+    font,
+    futures::channel::oneshot,
+    theme,
+    widget::{button, text, text_input, Column, Row},
+    Color, Element, Font, Length,
+};
+
+use crate::fuzzy;
+use crate::Message;
+
+/// How many files are listed at once
+const VISIBLE_ENTRIES: usize = 10;
+/// The most files scored for a query. Past this, matching files are kept to
+/// narrow down further but aren't ranked.
+const MAX_SCORED: usize = 10_000;
+/// The most matches listed, best first
+const MAX_MATCHES: usize = 1_000;
+
+const BOLD: Font = Font {
+    weight: font::Weight::Bold,
+    ..Font::DEFAULT
+};
+
+pub fn input_id() -> text_input::Id {
+    text_input::Id::new("finder")
+}
+
+/// The files the finder searches, which is kept between openings
+#[derive(Debug, Default)]
+pub struct Index {
+    files: Vec<PathBuf>,
+    // Each file's path as it's shown and matched
+    names: Vec<String>,
+}
+
+/// Every file under `root` that git doesn't ignore, relative to it. The
+/// walk happens on a thread of its own, as big projects take a while.
+pub async fn index(root: PathBuf) -> Arc<Index> {
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let files = walk(&root);
+        let names = files
+            .iter()
+            .map(|file| file.to_string_lossy().replace('\\', "/"))
+            .collect();
+        let _ = sender.send(Index { files, names });
+    });
+    Arc::new(receiver.await.unwrap_or_default())
+}
+
+fn walk(root: &Path) -> Vec<PathBuf> {
+    ignore::WalkBuilder::new(root)
+        .build()
+        // Anything that can't be read is left out
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+        .filter_map(|entry| Some(entry.path().strip_prefix(root).ok()?.to_path_buf()))
+        .collect()
+}
+
+/// `name` with the characters at `positions` in bold, and in `color` if
+/// there is one
+fn highlighted(name: &str, positions: &[usize], color: Option<Color>) -> Row<'static, Message> {
+    let part = |segment: String, is_match: bool| {
+        let part = text(segment);
+        match (is_match, color) {
+            (false, _) => part,
+            (true, None) => part.font(BOLD),
+            (true, Some(color)) => part.font(BOLD).style(color),
+        }
+    };
+    let mut row = Row::new();
+    let mut segment = String::new();
+    let mut is_match = false;
+    let mut positions = positions.iter().peekable();
+    for (i, c) in name.chars().enumerate() {
+        let matched = positions.next_if_eq(&&i).is_some();
+        if matched != is_match && !segment.is_empty() {
+            row = row.push(part(std::mem::take(&mut segment), is_match));
+        }
+        is_match = matched;
+        segment.push(c);
+    }
+    row.push(part(segment, is_match))
+}
+
+/// The quick open overlay, which finds a file in the project by its path
+#[derive(Debug, Default)]
+pub struct Finder {
+    query: String,
+    // `None` until the project's been walked
+    index: Option<Arc<Index>>,
+    // Every file that matches the query, in the order of the index
+    candidates: Vec<usize>,
+    // The best of the candidates, best first
+    matches: Vec<usize>,
+    // Index into `matches`
+    selected: usize,
+    matcher: fuzzy::Matcher,
+}
+
+impl Finder {
+    /// Search the files in `index`
+    pub fn set_index(&mut self, index: Arc<Index>) {
+        self.candidates = (0..index.names.len()).collect();
+        self.index = Some(index);
+        self.search(self.query.clone());
+    }
+
+    /// Show the files that match `query`, selecting the best
+    pub fn set_query(&mut self, query: String) {
+        // Typing more can only rule files out, so only the ones that still
+        // match need checking
+        if !query.starts_with(&self.query) {
+            let count = self.index.as_ref().map_or(0, |index| index.names.len());
+            self.candidates = (0..count).collect();
+        }
+        self.search(query);
+    }
+
+    /// Narrow the candidates down to the files that match `query`, and rank
+    /// the first of them
+    fn search(&mut self, query: String) {
+        self.selected = 0;
+        self.query = query;
+        let Some(index) = &self.index else {
+            return;
+        };
+        let (query, matcher) = (&self.query, &mut self.matcher);
+        let mut scored: Vec<(i64, usize)> = Vec::new();
+        self.candidates.retain(|&i| {
+            if scored.len() == MAX_SCORED {
+                return matcher.is_match(query, &index.names[i]);
+            }
+            let Some(score) = matcher.score(query, &index.names[i]) else {
+                return false;
+            };
+            scored.push((score, i));
+            true
+        });
+        // Shorter paths win ties, as less of them went unmatched
+        let rank = |&(score, i): &(i64, usize)| (-score, index.names[i].len());
+        if scored.len() > MAX_MATCHES {
+            scored.select_nth_unstable_by_key(MAX_MATCHES, rank);
+            scored.truncate(MAX_MATCHES);
+        }
+        scored.sort_by_key(rank);
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+    }
+
+    /// Move the selection `by` files, wrapping around at the ends
+    pub fn move_selection(&mut self, by: isize) {
+        if !self.matches.is_empty() {
+            let count = self.matches.len() as isize;
+            self.selected = (self.selected as isize + by).rem_euclid(count) as usize;
+        }
+    }
+
+    /// The path of the file at `index`
+    pub fn path(&self, index: usize) -> Option<PathBuf> {
+        self.index.as_ref()?.files.get(index).cloned()
+    }
+
+    /// The matching parts of each path are shown in `color`, except on the
+    /// selected one
+    pub fn view(&self, color: Color) -> Element<'_, Message> {
+        let names = self.index.as_ref().map_or(&[][..], |index| &index.names);
+        let selected = self.matches.get(self.selected).copied();
+        let mut input = text_input("Type a file name", &self.query)
+            .id(input_id())
+            .on_input(Message::FinderChanged);
+        if let Some(selected) = selected {
+            input = input.on_submit(Message::OpenFoundFile(selected));
+        }
+        let mut list = Column::new();
+        // Scrolled just far enough to show the selection
+        let first = self.selected.saturating_sub(VISIBLE_ENTRIES - 1);
+        for &index in self.matches.iter().skip(first).take(VISIBLE_ENTRIES) {
+            let name = &names[index];
+            let positions = fuzzy::find(&self.query, name)
+                .map(|found| found.positions)
+                .unwrap_or_default();
+            let (style, color) = if Some(index) == selected {
+                (theme::Button::Primary, None)
+            } else {
+                (theme::Button::Text, Some(color))
+            };
+            list = list.push(
+                button(highlighted(name, &positions, color))
+                    .width(Length::Fill)
+                    .style(style)
+                    .on_press(Message::OpenFoundFile(index)),
+            );
+        }
+        if self.index.is_none() {
+            list = list.push(text("Indexing files..."));
+        } else if self.matches.is_empty() {
+            list = list.push(text("No matching files"));
+        }
+        Column::new().spacing(10).push(input).push(list).into()
+    }
+}
@@ -0,0 +1,166 @@
+/// Points for each character of the pattern that's matched
+const SCORE_MATCH: i64 = 16;
+/// Extra points for a match right after the one before it
+const BONUS_CONSECUTIVE: i64 = 8;
+/// Extra points for a match at the start of a word
+const BONUS_WORD_START: i64 = 10;
+/// Points lost for each character skipped between two matches
+const PENALTY_GAP: i64 = 1;
+
+/// A candidate the pattern was found in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Higher is better
+    pub score: i64,
+    /// Which characters of the candidate were matched, by char index
+    pub positions: Vec<usize>,
+}
+
+/// Find the characters of `pattern` in order in `candidate`, preferring
+/// matches that are consecutive or start words. Like isearch, it's only case
+/// sensitive once the pattern has capitals in it.
+pub fn find(pattern: &str, candidate: &str) -> Option<Match> {
+    Matcher::default().find(pattern, candidate)
+}
+
+/// `c` in lowercase, unless the match is `case_sensitive`
+fn fold(c: char, case_sensitive: bool) -> char {
+    if case_sensitive {
+        c
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+/// Finds patterns in many candidates, reusing its tables between them
+#[derive(Debug, Default)]
+pub struct Matcher {
+    // The pattern being found, folded to lowercase unless `case_sensitive`
+    pattern: Vec<char>,
+    case_sensitive: bool,
+    chars: Vec<char>,
+    bonus: Vec<i64>,
+    // `scores[i * n + j]` is the best score for the first `i + 1`
+    // characters of the pattern with the last one at `j`, where `n` is the
+    // length of the candidate, and `from` is where the one before it went
+    scores: Vec<Option<i64>>,
+    from: Vec<usize>,
+}
+
+impl Matcher {
+    /// Like `find`, with the tables it needs kept for next time
+    pub fn find(&mut self, pattern: &str, candidate: &str) -> Option<Match> {
+        let score = self.score(pattern, candidate)?;
+        let n = self.chars.len();
+        let mut positions = vec![0; self.pattern.len()];
+        if let Some(last) = self.pattern.len().checked_sub(1) {
+            let row = &self.scores[last * n..(last + 1) * n];
+            let mut j = row.iter().rposition(|best| *best == Some(score))?;
+            for i in (0..self.pattern.len()).rev() {
+                positions[i] = j;
+                j = self.from[i * n + j];
+            }
+        }
+        Some(Match { score, positions })
+    }
+
+    /// Whether `candidate` has the characters of `pattern` in order, which
+    /// is much quicker to tell than its score
+    pub fn is_match(&mut self, pattern: &str, candidate: &str) -> bool {
+        self.set_pattern(pattern);
+        let mut rest = candidate.chars();
+        self.pattern
+            .iter()
+            .all(|&p| rest.by_ref().any(|c| fold(c, self.case_sensitive) == p))
+    }
+
+    fn set_pattern(&mut self, pattern: &str) {
+        self.case_sensitive = pattern.chars().any(char::is_uppercase);
+        self.pattern.clear();
+        let case_sensitive = self.case_sensitive;
+        self.pattern.extend(
+            pattern
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .map(|c| fold(c, case_sensitive)),
+        );
+    }
+
+    /// The score `find` would give, without working out the positions
+    pub fn score(&mut self, pattern: &str, candidate: &str) -> Option<i64> {
+        // Most candidates don't match at all, which is quick to rule out
+        if !self.is_match(pattern, candidate) {
+            return None;
+        }
+        if self.pattern.is_empty() {
+            return Some(0);
+        }
+        let case_sensitive = self.case_sensitive;
+
+        let (pattern, chars) = (&self.pattern, &mut self.chars);
+        chars.clear();
+        chars.extend(candidate.chars());
+        let n = chars.len();
+        self.bonus.clear();
+        self.bonus.extend((0..n).map(|i| {
+            let previous = i.checked_sub(1).map(|i| chars[i]);
+            match previous {
+                None => BONUS_WORD_START,
+                Some(previous) if !previous.is_alphanumeric() => BONUS_WORD_START,
+                Some(previous) if previous.is_lowercase() && chars[i].is_uppercase() => {
+                    BONUS_WORD_START
+                }
+                Some(previous) if !previous.is_ascii_digit() && chars[i].is_ascii_digit() => {
+                    BONUS_WORD_START
+                }
+                Some(_) => 0,
+            }
+        }));
+
+        let (scores, from) = (&mut self.scores, &mut self.from);
+        scores.clear();
+        scores.resize(pattern.len() * n, None);
+        from.clear();
+        from.resize(pattern.len() * n, 0);
+        for (i, &p) in pattern.iter().enumerate() {
+            // The best score of the row above so far, adjusted so the gap from
+            // it can be taken off by subtracting the column
+            let mut best: Option<(i64, usize)> = None;
+            for j in 0..n {
+                if i > 0 && j > 0 {
+                    if let Some(score) = scores[(i - 1) * n + j - 1] {
+                        let adjusted = score + (j - 1) as i64 * PENALTY_GAP;
+                        if best.is_none_or(|(best, _)| adjusted > best) {
+                            best = Some((adjusted, j - 1));
+                        }
+                    }
+                }
+                if fold(chars[j], case_sensitive) != p {
+                    continue;
+                }
+                let score = SCORE_MATCH + self.bonus[j];
+                scores[i * n + j] = if i == 0 {
+                    Some(score)
+                } else {
+                    let consecutive = j
+                        .checked_sub(1)
+                        .and_then(|k| scores[(i - 1) * n + k])
+                        .map(|previous| (previous + BONUS_CONSECUTIVE, j - 1));
+                    let gapped =
+                        best.map(|(adjusted, k)| (adjusted - (j - 1) as i64 * PENALTY_GAP, k));
+                    let previous = consecutive
+                        .into_iter()
+                        .chain(gapped)
+                        .max_by_key(|(s, _)| *s);
+                    previous.map(|(previous, k)| {
+                        from[i * n + j] = k;
+                        previous + score
+                    })
+                };
+            }
+        }
+
+        let last = pattern.len() - 1;
+        scores[last * n..].iter().filter_map(|score| *score).max()
+    }
+}
@@ -1,38 +1,626 @@
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
+use file_tree::FileTree;
+use finder::Finder;
 use iced::{
-    widget::{button, container, Column},
-    Element, Length, Sandbox, Theme,
+    advanced::widget,
+    clipboard, event, executor, keyboard, mouse, subscription, theme,
+    widget::{
+        button, container, mouse_area,
+        pane_grid::{self, PaneGrid},
+        text, text_input, Column, Row,
+    },
+    window, Alignment, Application, Color, Command, Element, Event, Length, Subscription, Theme,
+};
+use palette::Palette;
+use text_editor::{
+    backend::{Action, Backend, SearchQuery, ViewId},
+    keymap::{Bindings, Keymap},
+    text_editor, LineNumbers,
 };
-use text_editor::text_editor;
 
+mod file_tree;
+mod finder;
+mod fuzzy;
+mod palette;
 mod text_editor;
 
+pub use text_editor::backend;
+pub use text_editor::keymap;
+
+/// How the editor is started
+#[derive(Debug, Default)]
+pub struct Flags {
+    /// The files to open, each in a buffer of its own
+    pub paths: Vec<PathBuf>,
+    pub keymap: Keymap,
+}
+
 pub struct Editor {
     theme: Theme,
-    content: Mutex<text_editor::backend::Backend>,
+    line_numbers: LineNumbers,
+    keymap: Keymap,
+    // From the keymap config, along with any problems in it
+    bindings: Bindings,
+    keymap_errors: Vec<String>,
+    vim: bool,
+    buffers: Vec<Buffer>,
+    next_buffer_id: usize,
+    panes: pane_grid::State<Pane>,
+    // The pane being edited in
+    focus: pane_grid::Pane,
+    next_pane_id: usize,
+    // The tab being dragged to somewhere else in the tab bar
+    dragged_tab: Option<usize>,
+    error: Option<String>,
+    pending_close: Option<CloseTarget>,
+    save_as: Option<SaveAs>,
+    find: Option<FindBar>,
+    palette: Option<Palette>,
+    finder: Option<Finder>,
+    // The project's files as of the last walk, shown while the finder walks
+    // them again to catch any changes since
+    file_index: Option<Arc<finder::Index>>,
+    file_tree: FileTree,
+    file_tree_shown: bool,
+}
+
+/// A file being edited, or an untitled one. Its path and whether it has
+/// unsaved changes are kept by the backend.
+struct Buffer {
+    // Stays the same as other buffers are opened, closed and moved around
+    id: usize,
+    content: Mutex<Backend>,
+}
+
+impl Buffer {
+    /// The backend, with `view`'s cursors the ones that are edited at
+    fn backend(&mut self, view: ViewId) -> &mut Backend {
+        let backend = self.content.get_mut().expect("Poisoned");
+        backend.set_view(view);
+        backend
+    }
+}
+
+/// A split of the window with an editor in it. Panes can show the same
+/// buffer, each with cursors of its own.
+#[derive(Debug)]
+struct Pane {
+    // Names the pane's view of its buffer
+    id: usize,
+    /// The id of the buffer it shows
+    buffer: usize,
+}
+
+impl Pane {
+    fn view(&self) -> ViewId {
+        ViewId(self.id)
+    }
+
+    /// The editor's id while it's showing its buffer
+    fn editor_id(&self) -> widget::Id {
+        widget::Id::new(format!("pane-{}-buffer-{}", self.id, self.buffer))
+    }
+}
+
+/// What's been entered in the find and replace bar
+#[derive(Debug, Default)]
+struct FindBar {
+    query: SearchQuery,
+    replacement: String,
+    // Why the query couldn't be searched for
+    error: Option<String>,
+}
+
+/// Where to save a buffer to, as it's being typed
+#[derive(Debug)]
+struct SaveAs {
+    /// The id of the buffer to save
+    buffer: usize,
+    path: String,
+    // Whether it's being saved to close it, which goes on once it's saved
+    closing: bool,
+}
+
+/// Something that can be closed, and may need to prompt about unsaved changes first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseTarget {
+    Window,
+    /// The buffer with this id
+    Buffer(usize),
+}
+
+/// The user's answer to the unsaved changes prompt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseChoice {
+    Save,
+    Discard,
+    Cancel,
 }
 
 #[derive(Clone, Debug)]
 pub enum Message {
     ChangeTheme,
+    ChangeLineNumbers,
+    ToggleVim,
+    KeymapChanged,
+    Save,
+    /// Ask where to save the buffer being edited
+    SaveAs,
+    SaveAsPathChanged(String),
+    ConfirmSaveAs,
+    CancelSaveAs,
+    RequestClose(CloseTarget),
+    ResolveClose(CloseChoice),
+    NewBuffer,
+    CloseActiveBuffer,
+    /// Switch to the buffer this many tabs along, wrapping around at the ends
+    SwitchBuffer(isize),
+    TabPressed(usize),
+    TabReleased(usize),
+    EndTabDrag,
+    SplitPane(pane_grid::Axis),
+    /// Close the focused pane, unless it's the only one
+    ClosePane,
+    /// Move the focus to the pane next to the focused one
+    FocusPane(pane_grid::Direction),
+    PaneClicked(pane_grid::Pane),
+    PaneResized(pane_grid::ResizeEvent),
+    OpenFind,
+    CloseFind,
+    FindChanged(String),
+    ReplacementChanged(String),
+    ToggleRegex,
+    ToggleCaseSensitive,
+    ToggleWholeWord,
+    FindNext,
+    FindPrevious,
+    Replace,
+    ReplaceAll,
+    OpenPalette,
+    OpenFinder,
+    FilesIndexed(Arc<finder::Index>),
+    FinderChanged(String),
+    MoveFinderSelection(isize),
+    OpenFoundFile(usize),
+    PaletteChanged(String),
+    MovePaletteSelection(isize),
+    RunPaletteEntry(usize),
+    /// Run an editor command outside of the editor, like from the palette
+    RunCommand(keymap::Command),
+    Pasted(Option<String>),
+    ToggleFileTree,
+    FileTreeClicked(PathBuf),
+    /// Files in the tree changed on disk
+    FileTreeChanged,
+    NewFile,
+    RenameFile,
+    DeleteFile,
+    FileNameChanged(String),
+    ConfirmFileOperation,
+    CancelFileOperation,
+    /// Close the palette or finder, or the find bar if neither is open
+    Escape,
+}
+
+fn find_input_id() -> text_input::Id {
+    text_input::Id::new("find")
+}
+
+fn save_as_input_id() -> text_input::Id {
+    text_input::Id::new("save-as")
+}
+
+fn buffer_name(backend: &Backend) -> String {
+    backend
+        .path()
+        .and_then(|path| path.file_name())
+        .map_or_else(
+            || "Untitled".to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+}
+
+/// `path` made absolute with any links resolved, so a file has the same path
+/// however it was opened. For a file that doesn't exist yet, its directory's
+/// are resolved.
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(path) = std::fs::canonicalize(path) {
+        return path;
+    }
+    let path = std::env::current_dir().unwrap_or_default().join(path);
+    let resolved = path
+        .parent()
+        .zip(path.file_name())
+        .and_then(|(dir, name)| Some(std::fs::canonicalize(dir).ok()?.join(name)));
+    resolved.unwrap_or(path)
+}
+
+/// The bindings from the keymap config, and its problems as messages to show
+fn load_keymap(keymap: Keymap) -> (Bindings, Vec<String>) {
+    match keymap::config::load(keymap) {
+        Ok((bindings, errors)) => (bindings, errors.iter().map(ToString::to_string).collect()),
+        Err(e) => (
+            Bindings::default(),
+            vec![format!("Failed to read the keymap config: {e}")],
+        ),
+    }
+}
+
+impl Editor {
+    fn focused(&self) -> &Pane {
+        self.panes
+            .get(&self.focus)
+            .expect("The focused pane is open")
+    }
+
+    /// The index into `buffers` of the one being edited
+    fn active(&self) -> usize {
+        let id = self.focused().buffer;
+        self.buffers
+            .iter()
+            .position(|buffer| buffer.id == id)
+            .unwrap_or_default()
+    }
+
+    /// The backend of the buffer being edited, at the focused pane's cursors
+    fn content(&mut self) -> &mut Backend {
+        let (index, view) = (self.active(), self.focused().view());
+        self.buffers[index].backend(view)
+    }
+
+    fn focus_editor(&self) -> Command<Message> {
+        text_editor::focus(self.focused().editor_id())
+    }
+
+    /// Focus `pane`, taking the find bar's search along
+    fn focus_pane(&mut self, pane: pane_grid::Pane) -> Command<Message> {
+        if self.find.is_some() {
+            self.content().clear_search();
+        }
+        self.focus = pane;
+        self.search();
+        self.focus_editor()
+    }
+
+    /// Add a buffer after the others, returning its index
+    fn add_buffer(&mut self, backend: Backend) -> usize {
+        self.buffers.push(Buffer {
+            id: self.next_buffer_id,
+            content: Mutex::new(backend),
+        });
+        self.next_buffer_id += 1;
+        self.buffers.len() - 1
+    }
+
+    /// Open the file at `path` in a buffer, or switch to it if it's open
+    /// already. An untitled buffer that hasn't been touched is replaced.
+    fn open(&mut self, path: PathBuf) {
+        let path = canonical(&path);
+        let open = self.buffers.iter_mut().position(|buffer| {
+            buffer.content.get_mut().expect("Poisoned").path() == Some(path.as_path())
+        });
+        if let Some(index) = open {
+            self.select(index);
+            return;
+        }
+        let backend = match Backend::from_path(&path) {
+            Ok(backend) => backend,
+            // Opening a file that doesn't exist yet creates it on save
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Backend::with_path(path),
+            Err(e) => {
+                self.error = Some(format!("Failed to open {}: {e}", path.display()));
+                return;
+            }
+        };
+        let active = self.content();
+        if active.path().is_none() && !active.is_dirty() {
+            *active = backend;
+            self.search();
+        } else {
+            let index = self.add_buffer(backend);
+            self.select(index);
+        }
+    }
+
+    /// Show the buffer at `index` in the focused pane, taking the find bar's
+    /// search along
+    fn select(&mut self, index: usize) {
+        if self.find.is_some() {
+            self.content().clear_search();
+        }
+        let id = self.buffers[index].id;
+        if let Some(pane) = self.panes.get_mut(&self.focus) {
+            pane.buffer = id;
+        }
+        self.search();
+    }
+
+    /// Search for the find bar's query after it changes
+    fn search(&mut self) {
+        let (index, view) = (self.active(), self.focused().view());
+        if let Some(find) = &mut self.find {
+            let backend = self.buffers[index].backend(view);
+            find.error = backend.set_search(&find.query).err().map(|e| e.to_string());
+        }
+    }
+
+    /// The names of the buffers `target` would close that have unsaved changes
+    fn unsaved(&self, target: CloseTarget) -> Vec<String> {
+        self.buffers
+            .iter()
+            .filter(|buffer| match target {
+                CloseTarget::Window => true,
+                CloseTarget::Buffer(id) => buffer.id == id,
+            })
+            .filter_map(|buffer| {
+                let backend = buffer.content.lock().expect("Poisoned");
+                backend.is_dirty().then(|| buffer_name(&backend))
+            })
+            .collect()
+    }
+
+    /// Save the buffers `target` would close that have a path, returning
+    /// the id of one without a path that's left to save
+    fn save(&mut self, target: CloseTarget) -> std::io::Result<Option<usize>> {
+        let mut untitled = None;
+        for buffer in &mut self.buffers {
+            if target == CloseTarget::Window || target == CloseTarget::Buffer(buffer.id) {
+                let backend = buffer.content.get_mut().expect("Poisoned");
+                if !backend.is_dirty() {
+                    continue;
+                }
+                if backend.path().is_some() {
+                    backend.save()?;
+                } else {
+                    untitled.get_or_insert(buffer.id);
+                }
+            }
+        }
+        Ok(untitled)
+    }
+
+    /// Ask where to save the buffer with id `id`, showing it while asking
+    fn start_save_as(&mut self, id: usize, closing: bool) -> Command<Message> {
+        let Some(index) = self.buffers.iter().position(|buffer| buffer.id == id) else {
+            return Command::none();
+        };
+        self.select(index);
+        let backend = self.buffers[index].content.get_mut().expect("Poisoned");
+        let path = backend
+            .path()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        self.save_as = Some(SaveAs {
+            buffer: id,
+            path,
+            closing,
+        });
+        text_input::focus(save_as_input_id())
+    }
+
+    /// Close `target` without checking for unsaved changes
+    fn close(&mut self, target: CloseTarget) -> Command<Message> {
+        match target {
+            CloseTarget::Window => window::close(),
+            CloseTarget::Buffer(id) => {
+                let Some(index) = self.buffers.iter().position(|buffer| buffer.id == id) else {
+                    return Command::none();
+                };
+                self.buffers.remove(index);
+                // There's always a buffer to edit
+                if self.buffers.is_empty() {
+                    self.add_buffer(Backend::default());
+                }
+                // Panes showing it show the tab that takes its place instead
+                let next = self.buffers[index.min(self.buffers.len() - 1)].id;
+                let mut closed = Vec::new();
+                for (_, pane) in self.panes.iter_mut() {
+                    closed.push(
+                        Pane {
+                            buffer: id,
+                            ..*pane
+                        }
+                        .editor_id(),
+                    );
+                    if pane.buffer == id {
+                        pane.buffer = next;
+                    }
+                }
+                self.dragged_tab = None;
+                self.search();
+                Command::batch([text_editor::forget(closed), self.focus_editor()])
+            }
+        }
+    }
+
+    fn tab_bar(&self) -> Element<'_, Message> {
+        let active = self.active();
+        let mut tabs = Row::new().spacing(5).align_items(Alignment::Center);
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            let backend = buffer.content.lock().expect("Poisoned");
+            let modified = if backend.is_dirty() { " *" } else { "" };
+            let tab = Row::new()
+                .spacing(5)
+                .align_items(Alignment::Center)
+                .push(text(format!("{}{modified}", buffer_name(&backend))))
+                .push(
+                    button("×")
+                        .padding([0, 5])
+                        .style(theme::Button::Text)
+                        .on_press(Message::RequestClose(CloseTarget::Buffer(buffer.id))),
+                );
+            let style = if index == active || self.dragged_tab == Some(index) {
+                theme::Container::Box
+            } else {
+                theme::Container::Transparent
+            };
+            // Pressing a tab switches to it, and letting go over another
+            // moves it there
+            tabs = tabs.push(
+                mouse_area(container(tab).padding(5).style(style))
+                    .on_press(Message::TabPressed(index))
+                    .on_release(Message::TabReleased(index)),
+            );
+        }
+        tabs.push(button("+").on_press(Message::NewBuffer)).into()
+    }
+
+    fn pane_grid(&self) -> Element<'_, Message> {
+        let split = self.panes.len() > 1;
+        PaneGrid::new(&self.panes, |id, pane, _| {
+            let buffer = self
+                .buffers
+                .iter()
+                .find(|buffer| buffer.id == pane.buffer)
+                .expect("Panes show open buffers");
+            let editor = text_editor(&buffer.content)
+                .id(pane.editor_id())
+                .view(pane.view())
+                .padding(10.0)
+                .line_numbers(self.line_numbers)
+                .keymap(self.keymap)
+                .bindings(&self.bindings)
+                .vim(self.vim)
+                .on_close_request(Message::RequestClose(CloseTarget::Window));
+            let content = pane_grid::Content::new(editor);
+            if !split {
+                return content;
+            }
+            // With more than one pane, each is named and the focused one stands out
+            let name = buffer_name(&buffer.content.lock().expect("Poisoned"));
+            let style = if id == self.focus {
+                theme::Container::Box
+            } else {
+                theme::Container::Transparent
+            };
+            content.title_bar(pane_grid::TitleBar::new(text(name)).padding(5).style(style))
+        })
+        .spacing(5)
+        .on_click(Message::PaneClicked)
+        .on_resize(10, Message::PaneResized)
+        .into()
+    }
+
+    fn find_bar(&self, find: &FindBar) -> Element<'_, Message> {
+        let toggle = |label, is_on, message| {
+            let style = if is_on {
+                theme::Button::Primary
+            } else {
+                theme::Button::Secondary
+            };
+            button(label).style(style).on_press(message)
+        };
+        let position = {
+            let mut backend = self.buffers[self.active()]
+                .content
+                .lock()
+                .expect("Poisoned");
+            backend.set_view(self.focused().view());
+            backend.search_position()
+        };
+        let status = match (&find.error, position) {
+            (Some(error), _) => error.clone(),
+            (None, Some((Some(selected), count))) => format!("{} of {count}", selected + 1),
+            (None, Some((None, 0))) => "No results".to_string(),
+            (None, Some((None, count))) => format!("{count} results"),
+            (None, None) => String::new(),
+        };
+        let find_row = Row::new()
+            .spacing(10)
+            .push(
+                text_input("Find", &find.query.text)
+                    .id(find_input_id())
+                    .on_input(Message::FindChanged)
+                    .on_submit(Message::FindNext),
+            )
+            .push(toggle(
+                "Aa",
+                find.query.case_sensitive,
+                Message::ToggleCaseSensitive,
+            ))
+            .push(toggle(
+                "Word",
+                find.query.whole_word,
+                Message::ToggleWholeWord,
+            ))
+            .push(toggle(".*", find.query.regex, Message::ToggleRegex))
+            .push(text(status))
+            .push(button("Previous").on_press(Message::FindPrevious))
+            .push(button("Next").on_press(Message::FindNext))
+            .push(button("Close").on_press(Message::CloseFind));
+        let replace_row = Row::new()
+            .spacing(10)
+            .push(
+                text_input("Replace", &find.replacement)
+                    .on_input(Message::ReplacementChanged)
+                    .on_submit(Message::Replace),
+            )
+            .push(button("Replace").on_press(Message::Replace))
+            .push(button("Replace All").on_press(Message::ReplaceAll));
+        Column::new()
+            .spacing(10)
+            .push(find_row)
+            .push(replace_row)
+            .into()
+    }
 }
 
-impl Sandbox for Editor {
+impl Application for Editor {
+    type Executor = executor::Default;
     type Message = Message;
+    type Theme = Theme;
+    type Flags = Flags;
 
-    fn new() -> Self {
-        Self {
+    fn new(Flags { paths, keymap }: Self::Flags) -> (Self, Command<Self::Message>) {
+        let (bindings, keymap_errors) = load_keymap(keymap);
+        // Showing the untitled buffer that's added first
+        let (panes, focus) = pane_grid::State::new(Pane { id: 0, buffer: 0 });
+        let mut editor = Self {
             theme: Theme::Dark,
-            content: Mutex::new(text_editor::backend::Backend::default()),
+            line_numbers: LineNumbers::default(),
+            keymap,
+            bindings,
+            keymap_errors,
+            vim: false,
+            buffers: Vec::new(),
+            next_buffer_id: 0,
+            panes,
+            focus,
+            next_pane_id: 1,
+            dragged_tab: None,
+            error: None,
+            pending_close: None,
+            save_as: None,
+            find: None,
+            palette: None,
+            finder: None,
+            file_index: None,
+            // Like the finder, the tree shows where the editor was started
+            file_tree: FileTree::new(std::env::current_dir().unwrap_or_default()),
+            file_tree_shown: false,
+        };
+        editor.add_buffer(Backend::default());
+        for path in paths {
+            editor.open(path);
         }
+        // The first file given is the one to start on
+        editor.select(0);
+        (editor, Command::none())
     }
 
     fn title(&self) -> String {
-        "Text Editor".to_string()
+        let backend = self.buffers[self.active()]
+            .content
+            .lock()
+            .expect("Poisoned");
+        let name = buffer_name(&backend);
+        let modified = if backend.is_dirty() { " *" } else { "" };
+        format!("{name}{modified} - Text Editor")
     }
 
-    fn update(&mut self, message: Self::Message) {
+    fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         match message {
             Message::ChangeTheme => {
                 self.theme = match self.theme {
@@ -41,15 +629,492 @@ impl Sandbox for Editor {
                     Theme::Custom(_) => unreachable!(),
                 }
             }
+            Message::ChangeLineNumbers => {
+                self.line_numbers = match self.line_numbers {
+                    LineNumbers::Absolute => LineNumbers::Relative,
+                    LineNumbers::Relative => LineNumbers::Hidden,
+                    LineNumbers::Hidden => LineNumbers::Absolute,
+                }
+            }
+            Message::ToggleVim => self.vim = !self.vim,
+            Message::KeymapChanged => {
+                (self.bindings, self.keymap_errors) = load_keymap(self.keymap)
+            }
+            Message::Save => {
+                // An untitled buffer has to be given a path first
+                if self.content().path().is_none() {
+                    return self.update(Message::SaveAs);
+                }
+                self.error = self
+                    .content()
+                    .save()
+                    .err()
+                    .map(|e| format!("Failed to save: {e}"));
+            }
+            Message::SaveAs => {
+                let id = self.focused().buffer;
+                return self.start_save_as(id, false);
+            }
+            Message::SaveAsPathChanged(path) => {
+                if let Some(save_as) = &mut self.save_as {
+                    save_as.path = path;
+                }
+            }
+            Message::ConfirmSaveAs => {
+                let Some(save_as) = self.save_as.take() else {
+                    return Command::none();
+                };
+                let Some(buffer) = self.buffers.iter_mut().find(|b| b.id == save_as.buffer) else {
+                    return Command::none();
+                };
+                let backend = buffer.content.get_mut().expect("Poisoned");
+                let result = if save_as.path.trim().is_empty() {
+                    Err(std::io::Error::other("No path was given"))
+                } else {
+                    backend.save_as(canonical(Path::new(save_as.path.trim())))
+                };
+                match result {
+                    Ok(()) => {
+                        self.error = None;
+                        if save_as.closing {
+                            return self.update(Message::ResolveClose(CloseChoice::Save));
+                        }
+                        return self.focus_editor();
+                    }
+                    // The prompt stays open to try another path
+                    Err(e) => {
+                        self.error = Some(format!("Failed to save: {e}"));
+                        self.save_as = Some(save_as);
+                    }
+                }
+            }
+            Message::CancelSaveAs => {
+                if self.save_as.take().is_some() {
+                    return self.focus_editor();
+                }
+            }
+            Message::RequestClose(target) => {
+                if self.unsaved(target).is_empty() {
+                    return self.close(target);
+                }
+                self.pending_close = Some(target);
+            }
+            Message::ResolveClose(choice) => {
+                let Some(target) = self.pending_close else {
+                    return Command::none();
+                };
+                match choice {
+                    // The prompt stays until everything is saved, so a buffer
+                    // that failed to save can be discarded or saved again
+                    CloseChoice::Save => match self.save(target) {
+                        Ok(None) => {
+                            self.pending_close = None;
+                            return self.close(target);
+                        }
+                        // Saving it goes on with the close
+                        Ok(Some(untitled)) => return self.start_save_as(untitled, true),
+                        Err(e) => self.error = Some(format!("Failed to save: {e}")),
+                    },
+                    CloseChoice::Discard => {
+                        self.pending_close = None;
+                        self.save_as.take_if(|save_as| save_as.closing);
+                        return self.close(target);
+                    }
+                    CloseChoice::Cancel => {
+                        self.pending_close = None;
+                        self.save_as.take_if(|save_as| save_as.closing);
+                    }
+                }
+            }
+            Message::NewBuffer => {
+                let index = self.add_buffer(Backend::default());
+                self.select(index);
+                return self.focus_editor();
+            }
+            Message::CloseActiveBuffer => {
+                let id = self.focused().buffer;
+                return self.update(Message::RequestClose(CloseTarget::Buffer(id)));
+            }
+            Message::SwitchBuffer(by) => {
+                let count = self.buffers.len() as isize;
+                self.select((self.active() as isize + by).rem_euclid(count) as usize);
+                return self.focus_editor();
+            }
+            Message::TabPressed(index) => {
+                self.select(index);
+                self.dragged_tab = Some(index);
+                return self.focus_editor();
+            }
+            Message::TabReleased(index) => {
+                if let Some(dragged) = self.dragged_tab.take() {
+                    let buffer = self.buffers.remove(dragged);
+                    self.buffers.insert(index, buffer);
+                }
+            }
+            Message::EndTabDrag => self.dragged_tab = None,
+            Message::SplitPane(axis) => {
+                // The new pane starts with the focused one's cursors
+                self.content();
+                let pane = Pane {
+                    id: self.next_pane_id,
+                    buffer: self.focused().buffer,
+                };
+                if let Some((pane, _)) = self.panes.split(axis, &self.focus, pane) {
+                    self.next_pane_id += 1;
+                    return self.focus_pane(pane);
+                }
+            }
+            Message::ClosePane => {
+                if self.panes.len() > 1 {
+                    if self.find.is_some() {
+                        self.content().clear_search();
+                    }
+                    if let Some((closed, sibling)) = self.panes.close(&self.focus) {
+                        for buffer in &mut self.buffers {
+                            let backend = buffer.content.get_mut().expect("Poisoned");
+                            backend.remove_view(closed.view());
+                        }
+                        self.focus = sibling;
+                        self.search();
+                        return self.focus_editor();
+                    }
+                }
+            }
+            Message::FocusPane(direction) => {
+                if let Some(pane) = self.panes.adjacent(&self.focus, direction) {
+                    return self.focus_pane(pane);
+                }
+            }
+            Message::PaneClicked(pane) => {
+                if pane != self.focus {
+                    return self.focus_pane(pane);
+                }
+            }
+            Message::PaneResized(pane_grid::ResizeEvent { split, ratio }) => {
+                self.panes.resize(&split, ratio);
+            }
+            Message::OpenFind => {
+                self.find.get_or_insert_with(FindBar::default);
+                self.search();
+                return text_input::focus(find_input_id());
+            }
+            Message::CloseFind => {
+                if self.find.is_some() {
+                    self.content().clear_search();
+                    self.find = None;
+                    return self.focus_editor();
+                }
+            }
+            Message::FindChanged(query) => {
+                if let Some(find) = &mut self.find {
+                    find.query.text = query;
+                    self.search();
+                }
+            }
+            Message::ReplacementChanged(replacement) => {
+                if let Some(find) = &mut self.find {
+                    find.replacement = replacement;
+                }
+            }
+            Message::ToggleRegex => {
+                if let Some(find) = &mut self.find {
+                    find.query.regex = !find.query.regex;
+                    self.search();
+                }
+            }
+            Message::ToggleCaseSensitive => {
+                if let Some(find) = &mut self.find {
+                    find.query.case_sensitive = !find.query.case_sensitive;
+                    self.search();
+                }
+            }
+            Message::ToggleWholeWord => {
+                if let Some(find) = &mut self.find {
+                    find.query.whole_word = !find.query.whole_word;
+                    self.search();
+                }
+            }
+            Message::FindNext => {
+                self.content().action(Action::FindNext);
+            }
+            Message::FindPrevious => {
+                self.content().action(Action::FindPrevious);
+            }
+            Message::Replace => {
+                let (index, view) = (self.active(), self.focused().view());
+                if let Some(find) = &self.find {
+                    let backend = self.buffers[index].backend(view);
+                    backend.replace_match(&find.replacement);
+                }
+            }
+            Message::ReplaceAll => {
+                let (index, view) = (self.active(), self.focused().view());
+                if let Some(find) = &self.find {
+                    let backend = self.buffers[index].backend(view);
+                    backend.replace_all(&find.replacement);
+                }
+            }
+            Message::OpenPalette => {
+                self.finder = None;
+                self.palette = Some(Palette::new(self.keymap, &self.bindings));
+                return text_input::focus(palette::input_id());
+            }
+            Message::PaletteChanged(query) => {
+                if let Some(palette) = &mut self.palette {
+                    palette.set_query(query);
+                }
+            }
+            Message::MovePaletteSelection(by) => {
+                if let Some(palette) = &mut self.palette {
+                    palette.move_selection(by);
+                }
+            }
+            Message::RunPaletteEntry(index) => {
+                if let Some(message) = self.palette.take().and_then(|p| p.message(index)) {
+                    // The command may move the focus on again, like Find does
+                    return Command::batch([self.focus_editor(), self.update(message)]);
+                }
+            }
+            Message::OpenFinder => {
+                self.palette = None;
+                let mut finder = Finder::default();
+                if let Some(index) = &self.file_index {
+                    finder.set_index(index.clone());
+                }
+                self.finder = Some(finder);
+                // Files are found relative to where the editor was started
+                let root = std::env::current_dir().unwrap_or_default();
+                return Command::batch([
+                    text_input::focus(finder::input_id()),
+                    Command::perform(finder::index(root), Message::FilesIndexed),
+                ]);
+            }
+            Message::FilesIndexed(index) => {
+                if let Some(finder) = &mut self.finder {
+                    finder.set_index(index.clone());
+                }
+                self.file_index = Some(index);
+            }
+            Message::FinderChanged(query) => {
+                if let Some(finder) = &mut self.finder {
+                    finder.set_query(query);
+                }
+            }
+            Message::MoveFinderSelection(by) => {
+                if let Some(finder) = &mut self.finder {
+                    finder.move_selection(by);
+                }
+            }
+            Message::OpenFoundFile(index) => {
+                if let Some(path) = self.finder.take().and_then(|f| f.path(index)) {
+                    self.open(path);
+                    return self.focus_editor();
+                }
+            }
+            Message::ToggleFileTree => {
+                self.file_tree_shown = !self.file_tree_shown;
+                // Changes while it was hidden weren't watched for
+                if self.file_tree_shown {
+                    self.file_tree.refresh();
+                }
+            }
+            Message::FileTreeClicked(path) => {
+                if let Some(path) = self.file_tree.click(path) {
+                    self.open(path);
+                    return self.focus_editor();
+                }
+            }
+            Message::FileTreeChanged => self.file_tree.refresh(),
+            Message::NewFile => {
+                self.file_tree.start_create();
+                return text_input::focus(file_tree::input_id());
+            }
+            Message::RenameFile => {
+                self.file_tree.start_rename();
+                return text_input::focus(file_tree::input_id());
+            }
+            Message::DeleteFile => self.file_tree.start_delete(),
+            Message::FileNameChanged(name) => self.file_tree.set_name(name),
+            Message::ConfirmFileOperation => match self.file_tree.confirm() {
+                Some(file_tree::Change::Created(path)) => {
+                    self.open(path);
+                    return self.focus_editor();
+                }
+                Some(file_tree::Change::Renamed { from, to }) => {
+                    // Buffers of the file, or of files in the directory, follow it
+                    let (from, to) = (canonical(&from), canonical(&to));
+                    for buffer in &mut self.buffers {
+                        let backend = buffer.content.get_mut().expect("Poisoned");
+                        let moved = backend
+                            .path()
+                            .and_then(|path| path.strip_prefix(&from).ok())
+                            .map(|rest| {
+                                // Joining nothing would leave a trailing separator
+                                if rest.as_os_str().is_empty() {
+                                    to.clone()
+                                } else {
+                                    to.join(rest)
+                                }
+                            });
+                        if let Some(path) = moved {
+                            backend.set_path(path);
+                        }
+                    }
+                }
+                Some(file_tree::Change::Deleted(path)) => {
+                    // Unsaved changes are kept, and saving them makes the file
+                    // again. Buffers with nothing to lose are closed.
+                    let path = canonical(&path);
+                    let deleted: Vec<usize> = self
+                        .buffers
+                        .iter()
+                        .filter(|buffer| {
+                            let backend = buffer.content.lock().expect("Poisoned");
+                            !backend.is_dirty()
+                                && backend.path().is_some_and(|file| file.starts_with(&path))
+                        })
+                        .map(|buffer| buffer.id)
+                        .collect();
+                    let closed = deleted
+                        .into_iter()
+                        .map(|id| self.close(CloseTarget::Buffer(id)))
+                        .collect::<Vec<_>>();
+                    return Command::batch(closed);
+                }
+                None => {}
+            },
+            Message::CancelFileOperation => self.file_tree.cancel(),
+            Message::RunCommand(command) => match command {
+                keymap::Command::Action(action) => self.content().action(action),
+                keymap::Command::Copy => return clipboard::write(self.content().copy()),
+                keymap::Command::Cut => return clipboard::write(self.content().cut()),
+                keymap::Command::Paste => return clipboard::read(Message::Pasted),
+                // The mark and kill ring live in the widget, so it runs these
+                keymap::Command::Emacs(command) => {
+                    return text_editor::run_emacs(self.focused().editor_id(), command);
+                }
+            },
+            Message::Pasted(text) => {
+                if let Some(text) = text {
+                    self.content().paste(&text);
+                }
+            }
+            Message::Escape => {
+                if self.palette.take().is_some()
+                    || self.finder.take().is_some()
+                    || self.save_as.take().is_some()
+                {
+                    return self.focus_editor();
+                }
+                return self.update(Message::CloseFind);
+            }
         }
+        Command::none()
     }
 
     fn view(&self) -> Element<'_, Self::Message> {
+        let files_button = button("Files").on_press(Message::ToggleFileTree);
+        let palette_button = button("Commands").on_press(Message::OpenPalette);
         let theme_button = button("Change Theme").on_press(Message::ChangeTheme);
-        let input = text_editor(&self.content).padding(10.0);
+        let line_numbers_button =
+            button("Change Line Numbers").on_press(Message::ChangeLineNumbers);
+        let vim_button =
+            button(if self.vim { "Vim: On" } else { "Vim: Off" }).on_press(Message::ToggleVim);
         let mut col = Column::new();
-        col = col.push(theme_button);
-        col = col.push(input);
+        col = col.push(
+            Row::new()
+                .spacing(10)
+                .push(files_button)
+                .push(palette_button)
+                .push(theme_button)
+                .push(line_numbers_button)
+                .push(vim_button),
+        );
+        if let Some(error) = &self.error {
+            col = col.push(text(error));
+        }
+        for error in &self.keymap_errors {
+            col = col.push(text(error));
+        }
+        if let Some(target) = self.pending_close {
+            let names = self.unsaved(target);
+            let has = if names.len() == 1 { "has" } else { "have" };
+            let choices = Row::new()
+                .spacing(10)
+                .push(button("Save").on_press(Message::ResolveClose(CloseChoice::Save)))
+                .push(button("Discard").on_press(Message::ResolveClose(CloseChoice::Discard)))
+                .push(button("Cancel").on_press(Message::ResolveClose(CloseChoice::Cancel)));
+            let prompt = Column::new()
+                .spacing(10)
+                .push(text(format!(
+                    "{} {has} unsaved changes. Save before closing?",
+                    names.join(", ")
+                )))
+                .push(choices);
+            col = col.push(container(prompt).padding(10).style(theme::Container::Box));
+        }
+        if let Some(save_as) = &self.save_as {
+            let name = self
+                .buffers
+                .iter()
+                .find(|buffer| buffer.id == save_as.buffer)
+                .map(|buffer| buffer_name(&buffer.content.lock().expect("Poisoned")))
+                .unwrap_or_default();
+            let prompt = Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(text(format!("Save {name} as")))
+                .push(
+                    text_input("Path", &save_as.path)
+                        .id(save_as_input_id())
+                        .on_input(Message::SaveAsPathChanged)
+                        .on_submit(Message::ConfirmSaveAs),
+                )
+                .push(button("Save").on_press(Message::ConfirmSaveAs))
+                .push(button("Cancel").on_press(Message::CancelSaveAs));
+            col = col.push(container(prompt).padding(10).style(theme::Container::Box));
+        }
+        if let Some(palette) = &self.palette {
+            col = col.push(
+                container(palette.view())
+                    .padding(10)
+                    .style(theme::Container::Box),
+            );
+        }
+        if let Some(finder) = &self.finder {
+            col = col.push(
+                container(finder.view(self.theme.palette().primary))
+                    .padding(10)
+                    .style(theme::Container::Box),
+            );
+        }
+        if let Some(find) = &self.find {
+            col = col.push(
+                container(self.find_bar(find))
+                    .padding(10)
+                    .style(theme::Container::Box),
+            );
+        }
+        let editor = Column::new().push(self.tab_bar()).push(self.pane_grid());
+        if self.file_tree_shown {
+            let palette = self.theme.palette();
+            let dimmed = Color {
+                a: 0.5,
+                ..palette.text
+            };
+            col = col.push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        container(self.file_tree.view(dimmed))
+                            .width(Length::Fixed(250.0))
+                            .height(Length::Fill),
+                    )
+                    .push(editor),
+            );
+        } else {
+            col = col.push(editor);
+        }
         container(col)
             .height(Length::Fill)
             .width(Length::Fill)
@@ -60,4 +1125,153 @@ impl Sandbox for Editor {
     fn theme(&self) -> Theme {
         self.theme.clone()
     }
+
+    fn subscription(&self) -> Subscription<Self::Message> {
+        // Shortcuts the editor has taken, like C-s in the Emacs keymap, are left alone
+        let keys = subscription::events_with(|event, status| match event {
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::S,
+                modifiers,
+            }) if modifiers.control() && status == event::Status::Ignored => {
+                Some(if modifiers.shift() {
+                    Message::SaveAs
+                } else {
+                    Message::Save
+                })
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::W,
+                modifiers,
+            }) if modifiers.control() && status == event::Status::Ignored => {
+                Some(if modifiers.shift() {
+                    Message::ClosePane
+                } else {
+                    Message::CloseActiveBuffer
+                })
+            }
+            // Ctrl+\ splits to the right, and Ctrl+Alt+\ downwards
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::Backslash,
+                modifiers,
+            }) if modifiers.control() && !modifiers.shift() && status == event::Status::Ignored => {
+                Some(Message::SplitPane(if modifiers.alt() {
+                    pane_grid::Axis::Horizontal
+                } else {
+                    pane_grid::Axis::Vertical
+                }))
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::B,
+                modifiers,
+            }) if modifiers.control() && status == event::Status::Ignored => {
+                Some(Message::ToggleFileTree)
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::N,
+                modifiers,
+            }) if modifiers.control() && status == event::Status::Ignored => {
+                Some(Message::NewBuffer)
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::Tab,
+                modifiers,
+            }) if modifiers.control() && status == event::Status::Ignored => {
+                Some(Message::SwitchBuffer(if modifiers.shift() {
+                    -1
+                } else {
+                    1
+                }))
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::F,
+                modifiers,
+            }) if modifiers.control() && status == event::Status::Ignored => {
+                Some(Message::OpenFind)
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::P,
+                modifiers,
+            }) if modifiers.control() && status == event::Status::Ignored => {
+                Some(if modifiers.shift() {
+                    Message::OpenPalette
+                } else {
+                    Message::OpenFinder
+                })
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::F3,
+                modifiers,
+            }) => Some(if modifiers.shift() {
+                Message::FindPrevious
+            } else {
+                Message::FindNext
+            }),
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::Escape,
+                ..
+            }) => Some(Message::Escape),
+            // Alt and an arrow move to the pane that way
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                modifiers,
+            }) if modifiers.alt()
+                && !modifiers.control()
+                && !modifiers.shift()
+                && status == event::Status::Ignored =>
+            {
+                let direction = match key_code {
+                    keyboard::KeyCode::Left => pane_grid::Direction::Left,
+                    keyboard::KeyCode::Right => pane_grid::Direction::Right,
+                    keyboard::KeyCode::Up => pane_grid::Direction::Up,
+                    keyboard::KeyCode::Down => pane_grid::Direction::Down,
+                    _ => return None,
+                };
+                Some(Message::FocusPane(direction))
+            }
+            _ => None,
+        });
+        let keymap_changes = keymap::config::watch().map(|()| Message::KeymapChanged);
+        let mut subscriptions = vec![keys, keymap_changes];
+        if self.palette.is_some() {
+            subscriptions.push(subscription::events_with(|event, _| match event {
+                Event::Keyboard(keyboard::Event::KeyPressed {
+                    key_code: keyboard::KeyCode::Up,
+                    ..
+                }) => Some(Message::MovePaletteSelection(-1)),
+                Event::Keyboard(keyboard::Event::KeyPressed {
+                    key_code: keyboard::KeyCode::Down,
+                    ..
+                }) => Some(Message::MovePaletteSelection(1)),
+                _ => None,
+            }));
+        }
+        if self.finder.is_some() {
+            subscriptions.push(subscription::events_with(|event, _| match event {
+                Event::Keyboard(keyboard::Event::KeyPressed {
+                    key_code: keyboard::KeyCode::Up,
+                    ..
+                }) => Some(Message::MoveFinderSelection(-1)),
+                Event::Keyboard(keyboard::Event::KeyPressed {
+                    key_code: keyboard::KeyCode::Down,
+                    ..
+                }) => Some(Message::MoveFinderSelection(1)),
+                _ => None,
+            }));
+        }
+        if self.file_tree_shown {
+            subscriptions.push(
+                file_tree::watch(self.file_tree.expanded()).map(|()| Message::FileTreeChanged),
+            );
+        }
+        if self.dragged_tab.is_some() {
+            // Letting go anywhere but over a tab puts the dragged one back
+            subscriptions.push(subscription::events_with(|event, _| match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                    Some(Message::EndTabDrag)
+                }
+                _ => None,
+            }));
+        }
+        Subscription::batch(subscriptions)
+    }
 }
@@ -1,7 +1,29 @@
-use iced::{Sandbox, Settings};
+use std::path::PathBuf;
 
-use text_editor::Editor;
+use iced::{Application, Settings};
+
+use text_editor::{Editor, Flags};
 
 fn main() -> iced::Result {
-    Editor::run(Settings::default())
+    let mut flags = Flags::default();
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--keymap" {
+            let name = args.next().unwrap_or_default();
+            flags.keymap = match name.to_string_lossy().parse() {
+                Ok(keymap) => keymap,
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(2);
+                }
+            };
+        } else {
+            flags.paths.push(PathBuf::from(arg));
+        }
+    }
+    Editor::run(Settings {
+        // Closing is handled by the editor so it can ask about unsaved changes
+        exit_on_close_request: false,
+        ..Settings::with_flags(flags)
+    })
 }
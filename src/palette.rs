@@ -0,0 +1,225 @@
+use iced::{
+    theme,
+    widget::{button, pane_grid, text, text_input, Column, Row},
+    Element, Length,
+};
+
+use crate::backend::Action;
+use crate::fuzzy;
+use crate::keymap::{Bindings, Chord, Command, Keymap, Lookup, APP_SHORTCUTS};
+use crate::{CloseTarget, Message};
+
+/// How many commands are listed at once
+const VISIBLE_ENTRIES: usize = 10;
+
+pub fn input_id() -> text_input::Id {
+    text_input::Id::new("palette")
+}
+
+/// A command the palette can run
+#[derive(Debug)]
+struct Entry {
+    title: String,
+    /// The keys that run it, as they're written in the keymap config
+    keys: Option<String>,
+    message: Message,
+}
+
+/// The command palette, which finds any command by name and shows the keys
+/// bound to it
+#[derive(Debug)]
+pub struct Palette {
+    query: String,
+    entries: Vec<Entry>,
+    // The entries that match the query, best first
+    matches: Vec<usize>,
+    // Index into `matches`
+    selected: usize,
+}
+
+/// The app's own commands. F3 is the only key they're given here, as the app
+/// sees it before the editor does. The rest are in `APP_SHORTCUTS`.
+fn app_commands() -> Vec<(&'static str, Option<&'static str>, Message)> {
+    vec![
+        ("Change Theme", None, Message::ChangeTheme),
+        ("Change Line Numbers", None, Message::ChangeLineNumbers),
+        ("Toggle Vim", None, Message::ToggleVim),
+        ("Reload Keymap", None, Message::KeymapChanged),
+        ("Save", None, Message::Save),
+        ("Save As", None, Message::SaveAs),
+        ("Go to File", None, Message::OpenFinder),
+        ("Toggle File Tree", None, Message::ToggleFileTree),
+        ("Find", None, Message::OpenFind),
+        ("Find Next", Some("f3"), Message::FindNext),
+        ("Find Previous", Some("shift+f3"), Message::FindPrevious),
+        ("New Buffer", None, Message::NewBuffer),
+        ("Close Buffer", None, Message::CloseActiveBuffer),
+        ("Next Buffer", None, Message::SwitchBuffer(1)),
+        ("Previous Buffer", None, Message::SwitchBuffer(-1)),
+        (
+            "Split Right",
+            None,
+            Message::SplitPane(pane_grid::Axis::Vertical),
+        ),
+        (
+            "Split Down",
+            None,
+            Message::SplitPane(pane_grid::Axis::Horizontal),
+        ),
+        ("Close Pane", None, Message::ClosePane),
+        (
+            "Focus Left Pane",
+            None,
+            Message::FocusPane(pane_grid::Direction::Left),
+        ),
+        (
+            "Focus Right Pane",
+            None,
+            Message::FocusPane(pane_grid::Direction::Right),
+        ),
+        (
+            "Focus Pane Above",
+            None,
+            Message::FocusPane(pane_grid::Direction::Up),
+        ),
+        (
+            "Focus Pane Below",
+            None,
+            Message::FocusPane(pane_grid::Direction::Down),
+        ),
+        ("Quit", None, Message::RequestClose(CloseTarget::Window)),
+    ]
+}
+
+/// The shortcut for the app command titled `title`, unless `keymap` or
+/// `bindings` give its chord to the editor, which then keeps it from the app
+fn app_shortcut(title: &str, keymap: Keymap, bindings: &Bindings) -> Option<String> {
+    let (_, keys) = APP_SHORTCUTS.iter().find(|(name, _)| *name == title)?;
+    let chord: Chord = keys.parse().ok()?;
+    match keymap.lookup(bindings, &[chord]) {
+        Lookup::Unbound => Some(keys.to_string()),
+        Lookup::Command(_) | Lookup::Prefix => None,
+    }
+}
+
+/// `select_word_left` as `Select Word Left`
+fn title(name: &str) -> String {
+    let words: Vec<String> = name
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+    words.join(" ")
+}
+
+impl Palette {
+    /// List the app's commands and the editor's, with the keys `keymap` and
+    /// `bindings` give them
+    pub fn new(keymap: Keymap, bindings: &Bindings) -> Self {
+        let sequences = keymap.key_sequences(bindings);
+        let mut entries: Vec<Entry> = app_commands()
+            .into_iter()
+            .map(|(title, keys, message)| Entry {
+                title: title.to_string(),
+                keys: keys
+                    .map(str::to_string)
+                    .or_else(|| app_shortcut(title, keymap, bindings)),
+                message,
+            })
+            .collect();
+        for (name, command) in Command::ALL {
+            // The app's Find Next and Find Previous do the same
+            if let Command::Action(Action::FindNext | Action::FindPrevious) = command {
+                continue;
+            }
+            let keys = sequences
+                .iter()
+                .find(|(_, bound)| bound == command)
+                .map(|(sequence, _)| {
+                    let chords: Vec<String> = sequence.iter().map(ToString::to_string).collect();
+                    chords.join(" ")
+                });
+            entries.push(Entry {
+                title: title(name),
+                keys,
+                message: Message::RunCommand(*command),
+            });
+        }
+        let mut palette = Self {
+            query: String::new(),
+            entries,
+            matches: Vec::new(),
+            selected: 0,
+        };
+        palette.set_query(String::new());
+        palette
+    }
+
+    /// Show the entries that match `query`, selecting the best
+    pub fn set_query(&mut self, query: String) {
+        let mut matches: Vec<(usize, i64)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| Some((i, fuzzy::find(&query, &entry.title)?.score)))
+            .collect();
+        // Shorter titles win ties, as less of them went unmatched
+        matches.sort_by_key(|&(i, score)| (-score, self.entries[i].title.len()));
+        self.matches = matches.into_iter().map(|(i, _)| i).collect();
+        self.selected = 0;
+        self.query = query;
+    }
+
+    /// Move the selection `by` entries, wrapping around at the ends
+    pub fn move_selection(&mut self, by: isize) {
+        if !self.matches.is_empty() {
+            let count = self.matches.len() as isize;
+            self.selected = (self.selected as isize + by).rem_euclid(count) as usize;
+        }
+    }
+
+    /// What running the entry at `index` does
+    pub fn message(&self, index: usize) -> Option<Message> {
+        self.entries.get(index).map(|entry| entry.message.clone())
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let selected = self.matches.get(self.selected).copied();
+        let mut input = text_input("Type a command", &self.query)
+            .id(input_id())
+            .on_input(Message::PaletteChanged);
+        if let Some(selected) = selected {
+            input = input.on_submit(Message::RunPaletteEntry(selected));
+        }
+        let mut list = Column::new();
+        // Scrolled just far enough to show the selection
+        let first = self.selected.saturating_sub(VISIBLE_ENTRIES - 1);
+        for &index in self.matches.iter().skip(first).take(VISIBLE_ENTRIES) {
+            let entry = &self.entries[index];
+            let row = Row::new()
+                .spacing(10)
+                .push(text(&entry.title).width(Length::Fill))
+                .push(text(entry.keys.as_deref().unwrap_or_default()));
+            let style = if Some(index) == selected {
+                theme::Button::Primary
+            } else {
+                theme::Button::Text
+            };
+            list = list.push(
+                button(row)
+                    .width(Length::Fill)
+                    .style(style)
+                    .on_press(Message::RunPaletteEntry(index)),
+            );
+        }
+        if self.matches.is_empty() {
+            list = list.push(text("No matching commands"));
+        }
+        Column::new().spacing(10).push(input).push(list).into()
+    }
+}
@@ -1,30 +1,88 @@
-use std::sync::Mutex;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
 use std::time::{Duration, Instant};
 
-use iced::advanced::layout::Node;
-use iced::widget::text::{draw as draw_text, Appearance as TextAppearance, LineHeight, Shaping};
+use std::ops::Range;
+
+use iced::widget::text::{LineHeight, Shaping};
 use iced::{
     advanced::{
-        layout, mouse, renderer,
+        layout,
+        mouse::{self, click},
+        renderer,
+        text::Text,
         widget::{self, tree},
         Clipboard, Layout, Shell, Widget,
     },
     event::Status,
-    keyboard::{Event as KeyEvent, KeyCode},
+    keyboard::{Event as KeyEvent, KeyCode, Modifiers},
     window::Event as WindowEvent,
-    Color, Element, Event, Length, Padding, Size,
+    Color, Command, Element, Event, Length, Padding, Size,
 };
-use iced::{alignment, window, Pixels, Rectangle, Vector};
+use iced::{alignment, window, Pixels, Point, Rectangle, Vector};
 
 pub mod backend;
 use backend::Backend;
+pub mod emacs;
+pub mod keymap;
+pub mod vim;
+
+use self::backend::{Action, Highlight, Position, Selection, Span, ViewId};
+use self::emacs::Emacs;
+use self::keymap::{Bindings, Chord, Command as KeyCommand, Keymap, Lookup};
+use self::vim::{Key, Mode, Vim};
+use unicode_segmentation::UnicodeSegmentation;
 
-use self::backend::Action;
+/// The color of each kind of highlighted text
+struct SyntaxColors {
+    keyword: Color,
+    type_: Color,
+    function: Color,
+    string: Color,
+    number: Color,
+    constant: Color,
+    comment: Color,
+    attribute: Color,
+    property: Color,
+    heading: Color,
+    emphasis: Color,
+    code: Color,
+    link: Color,
+}
+
+impl SyntaxColors {
+    fn color(&self, highlight: Highlight) -> Color {
+        match highlight {
+            Highlight::Keyword => self.keyword,
+            Highlight::Type => self.type_,
+            Highlight::Function => self.function,
+            Highlight::String => self.string,
+            Highlight::Number => self.number,
+            Highlight::Constant => self.constant,
+            Highlight::Comment => self.comment,
+            Highlight::Attribute => self.attribute,
+            Highlight::Property => self.property,
+            Highlight::Heading => self.heading,
+            Highlight::Emphasis => self.emphasis,
+            Highlight::Code => self.code,
+            Highlight::Link => self.link,
+        }
+    }
+}
 
 struct Appearance {
     background_color: Option<Color>,
     border_color: Option<Color>,
     text_color: Color,
+    selection_color: Color,
+    match_color: Color,
+    scrollbar_color: Color,
+    gutter_color: Color,
+    line_number_color: Color,
+    active_line_number_color: Color,
+    active_line_color: Color,
+    syntax: SyntaxColors,
 }
 
 trait Stylesheet {
@@ -38,11 +96,55 @@ impl Stylesheet for iced::Theme {
                 background_color: Some(Color::WHITE),
                 border_color: Some(Color::from_rgb(0.75, 1.0, 0.75)),
                 text_color: Color::BLACK,
+                selection_color: Color::from_rgb(0.7, 0.85, 1.0),
+                match_color: Color::from_rgba(1.0, 0.75, 0.0, 0.4),
+                scrollbar_color: Color::from_rgba(0.0, 0.0, 0.0, 0.3),
+                gutter_color: Color::from_rgb(0.95, 0.95, 0.95),
+                line_number_color: Color::from_rgb(0.6, 0.6, 0.6),
+                active_line_number_color: Color::BLACK,
+                active_line_color: Color::from_rgb(0.96, 0.96, 0.9),
+                syntax: SyntaxColors {
+                    keyword: Color::from_rgb(0.65, 0.15, 0.6),
+                    type_: Color::from_rgb(0.1, 0.45, 0.55),
+                    function: Color::from_rgb(0.25, 0.3, 0.75),
+                    string: Color::from_rgb(0.3, 0.55, 0.15),
+                    number: Color::from_rgb(0.7, 0.4, 0.0),
+                    constant: Color::from_rgb(0.7, 0.4, 0.0),
+                    comment: Color::from_rgb(0.55, 0.55, 0.55),
+                    attribute: Color::from_rgb(0.55, 0.45, 0.1),
+                    property: Color::from_rgb(0.75, 0.2, 0.25),
+                    heading: Color::from_rgb(0.75, 0.2, 0.25),
+                    emphasis: Color::from_rgb(0.65, 0.15, 0.6),
+                    code: Color::from_rgb(0.3, 0.55, 0.15),
+                    link: Color::from_rgb(0.25, 0.3, 0.75),
+                },
             },
             iced::Theme::Dark => Appearance {
                 background_color: Some(Color::BLACK),
                 border_color: Some(Color::from_rgb(0.25, 0.75, 0.25)),
                 text_color: Color::WHITE,
+                selection_color: Color::from_rgb(0.15, 0.3, 0.5),
+                match_color: Color::from_rgba(0.9, 0.65, 0.1, 0.35),
+                scrollbar_color: Color::from_rgba(1.0, 1.0, 1.0, 0.3),
+                gutter_color: Color::from_rgb(0.08, 0.08, 0.08),
+                line_number_color: Color::from_rgb(0.45, 0.45, 0.45),
+                active_line_number_color: Color::WHITE,
+                active_line_color: Color::from_rgb(0.1, 0.1, 0.13),
+                syntax: SyntaxColors {
+                    keyword: Color::from_rgb(0.78, 0.47, 0.87),
+                    type_: Color::from_rgb(0.9, 0.75, 0.48),
+                    function: Color::from_rgb(0.38, 0.69, 0.94),
+                    string: Color::from_rgb(0.6, 0.76, 0.47),
+                    number: Color::from_rgb(0.82, 0.6, 0.4),
+                    constant: Color::from_rgb(0.82, 0.6, 0.4),
+                    comment: Color::from_rgb(0.5, 0.52, 0.56),
+                    attribute: Color::from_rgb(0.86, 0.74, 0.45),
+                    property: Color::from_rgb(0.88, 0.42, 0.46),
+                    heading: Color::from_rgb(0.88, 0.42, 0.46),
+                    emphasis: Color::from_rgb(0.78, 0.47, 0.87),
+                    code: Color::from_rgb(0.6, 0.76, 0.47),
+                    link: Color::from_rgb(0.38, 0.69, 0.94),
+                },
             },
             iced::Theme::Custom(_) => {
                 let palette = self.palette();
@@ -50,6 +152,46 @@ impl Stylesheet for iced::Theme {
                     background_color: Some(palette.background),
                     border_color: Some(palette.primary),
                     text_color: palette.text,
+                    selection_color: Color {
+                        a: 0.4,
+                        ..palette.primary
+                    },
+                    match_color: Color {
+                        a: 0.35,
+                        ..palette.success
+                    },
+                    scrollbar_color: Color {
+                        a: 0.3,
+                        ..palette.text
+                    },
+                    gutter_color: palette.background,
+                    line_number_color: Color {
+                        a: 0.5,
+                        ..palette.text
+                    },
+                    active_line_number_color: palette.text,
+                    active_line_color: Color {
+                        a: 0.06,
+                        ..palette.text
+                    },
+                    syntax: SyntaxColors {
+                        keyword: palette.primary,
+                        type_: palette.success,
+                        function: palette.primary,
+                        string: palette.success,
+                        number: palette.danger,
+                        constant: palette.danger,
+                        comment: Color {
+                            a: 0.5,
+                            ..palette.text
+                        },
+                        attribute: palette.danger,
+                        property: palette.primary,
+                        heading: palette.primary,
+                        emphasis: palette.danger,
+                        code: palette.success,
+                        link: palette.primary,
+                    },
                 }
             }
         }
@@ -58,47 +200,597 @@ impl Stylesheet for iced::Theme {
 
 pub struct State {
     is_focused: bool,
+    // Whether the editor had the focus when the window lost it, so only
+    // that one gets it back
+    was_focused: bool,
     focus_start: Instant,
     now: Instant,
+    modifiers: Modifiers,
+    last_click: Option<mouse::Click>,
+    is_dragging: bool,
+    scroll_offset: Vector,
+    // The scrollbar being dragged, and how far along its thumb it was grabbed
+    scrollbar_drag: Option<(Axis, f32)>,
+    // The selection when the editor last handled an event, so changes made
+    // by the application can be scrolled into view
+    selection: Selection,
+    reveal_cursor: bool,
+    // The modal layer, while it's turned on
+    vim: Option<Vim>,
+    // The mark and kill ring, once an Emacs command has been used
+    emacs: Option<Emacs>,
+    // An Emacs command sent by `run_emacs`, which waits for the next event
+    // as it needs the clipboard
+    queued: Option<emacs::Command>,
+    // The start of a sequence of chords that's being typed
+    chords: Vec<Chord>,
+    // The id the editor was last shown with, which names the buffer in it
+    id: Option<widget::Id>,
+    // What's been put away of the other buffers shown before, by id
+    views: HashMap<widget::Id, View>,
+}
+
+/// What the editor keeps of a buffer while another is shown in its place
+struct View {
+    scroll_offset: Vector,
+    selection: Selection,
+    vim: Option<Vim>,
+}
+
+impl State {
+    /// Put away the view of the buffer being shown, and bring back the one
+    /// for `id` if it's been shown before. The cursor starts a fresh blink.
+    fn switch_to(&mut self, id: Option<widget::Id>) {
+        let view = View {
+            scroll_offset: self.scroll_offset,
+            selection: self.selection,
+            vim: self.vim.take(),
+        };
+        if let Some(previous) = std::mem::replace(&mut self.id, id) {
+            self.views.insert(previous, view);
+        }
+        let view = self.id.as_ref().and_then(|id| self.views.remove(id));
+        (self.scroll_offset, self.selection, self.vim) = match view {
+            Some(view) => (view.scroll_offset, view.selection, view.vim),
+            None => (Vector::new(0.0, 0.0), Selection::default(), None),
+        };
+        self.focus_start = Instant::now();
+        self.last_click = None;
+        self.is_dragging = false;
+        self.scrollbar_drag = None;
+        self.chords.clear();
+    }
+}
+
+impl widget::operation::Focusable for State {
+    fn is_focused(&self) -> bool {
+        self.is_focused
+    }
+
+    fn focus(&mut self) {
+        self.is_focused = true;
+        self.focus_start = Instant::now();
+    }
+
+    fn unfocus(&mut self) {
+        self.is_focused = false;
+        self.was_focused = false;
+    }
 }
 
 impl Default for State {
     fn default() -> Self {
         Self {
             is_focused: true,
+            was_focused: false,
             focus_start: Instant::now(),
             now: Instant::now(),
+            modifiers: Modifiers::default(),
+            last_click: None,
+            is_dragging: false,
+            scroll_offset: Vector::new(0.0, 0.0),
+            scrollbar_drag: None,
+            selection: Selection::default(),
+            reveal_cursor: false,
+            vim: None,
+            emacs: None,
+            queued: None,
+            chords: Vec::new(),
+            id: None,
+            views: HashMap::new(),
+        }
+    }
+}
+
+/// The width of `text` when drawn in the editor
+fn measure_width<Renderer>(renderer: &Renderer, text: &str) -> f32
+where
+    Renderer: iced::advanced::text::Renderer,
+{
+    renderer.measure_width(
+        text,
+        renderer.default_size(),
+        renderer.default_font(),
+        Shaping::Advanced,
+    )
+}
+
+/// The height of a row of text in the editor
+fn line_height<Renderer>(renderer: &Renderer) -> f32
+where
+    Renderer: iced::advanced::text::Renderer,
+{
+    LineHeight::default()
+        .to_absolute(Pixels::from(renderer.default_size()))
+        .into()
+}
+
+/// The document position closest to `point`, measured the same way as the
+/// text is laid out in `draw`
+fn hit_test<Renderer>(
+    renderer: &Renderer,
+    backend: &Backend,
+    text_bounds: Rectangle,
+    point: Point,
+) -> Position
+where
+    Renderer: iced::advanced::text::Renderer,
+{
+    let row = ((point.y - text_bounds.y) / line_height(renderer)).max(0.0) as usize;
+    let row = row.min(backend.line_count().saturating_sub(1));
+    let line = backend.display_line(row);
+    let x = point.x - text_bounds.x;
+    let x_of = |column| {
+        let display_column = backend.display_column(Position { row, column });
+        measure_width(renderer, &line[..display_column])
+    };
+
+    // Find the first column at or past the point, then see whether the one
+    // before it is closer
+    let (mut low, mut high) = (0, backend.line_length(row));
+    while low < high {
+        let middle = (low + high) / 2;
+        if x_of(middle) < x {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    let column = if low > 0 && x - x_of(low - 1) < x_of(low) - x {
+        low - 1
+    } else {
+        low
+    };
+    Position { row, column }
+}
+
+fn fill_rectangle<Renderer>(renderer: &mut Renderer, bounds: Rectangle, color: Color)
+where
+    Renderer: renderer::Renderer,
+{
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0.into(),
+            border_width: 0.0f32,
+            border_color: Color::TRANSPARENT,
+        },
+        color,
+    );
+}
+
+/// Split a row of `length` bytes into runs of one color, drawing the text
+/// between highlighted spans in the plain text color
+fn color_runs(
+    length: usize,
+    spans: &[Span],
+    appearance: &Appearance,
+) -> Vec<(Range<usize>, Color)> {
+    let mut runs = Vec::new();
+    let mut end = 0;
+    for span in spans {
+        if span.range.start > end {
+            runs.push((end..span.range.start, appearance.text_color));
+        }
+        runs.push((span.range.clone(), appearance.syntax.color(span.highlight)));
+        end = span.range.end;
+    }
+    if end < length {
+        runs.push((end..length, appearance.text_color));
+    }
+    runs
+}
+
+/// The rows that can be seen when scrolled down by `offset_y`
+fn visible_rows(
+    backend: &Backend,
+    text_bounds: Rectangle,
+    offset_y: f32,
+    line_height: f32,
+) -> Range<usize> {
+    let first = (offset_y / line_height).floor() as usize;
+    let last = ((offset_y + text_bounds.height) / line_height).ceil() as usize;
+    first.min(backend.line_count())..last.min(backend.line_count())
+}
+
+/// The size of the whole document. Only the visible rows are measured for
+/// the width, so large documents don't have to be laid out.
+fn content_size<Renderer>(
+    renderer: &Renderer,
+    backend: &Backend,
+    text_bounds: Rectangle,
+    offset_y: f32,
+) -> Size
+where
+    Renderer: iced::advanced::text::Renderer,
+{
+    let height = line_height(renderer);
+    let width = visible_rows(backend, text_bounds, offset_y, height)
+        .map(|row| measure_width(renderer, &backend.display_line(row)))
+        .fold(0.0, f32::max);
+    Size::new(width + CURSOR_WIDTH, backend.line_count() as f32 * height)
+}
+
+/// Keep a scroll offset within the content
+fn clamp_scroll<Renderer>(
+    renderer: &Renderer,
+    backend: &Backend,
+    text_bounds: Rectangle,
+    offset: Vector,
+) -> Vector
+where
+    Renderer: iced::advanced::text::Renderer,
+{
+    let max_y = backend.line_count() as f32 * line_height(renderer) - text_bounds.height;
+    let y = offset.y.min(max_y).max(0.0);
+    let content = content_size(renderer, backend, text_bounds, y);
+    let x = offset.x.min(content.width - text_bounds.width).max(0.0);
+    Vector::new(x, y)
+}
+
+/// The smallest change to `offset` that brings the cursor into view
+fn scroll_to_cursor<Renderer>(
+    renderer: &Renderer,
+    backend: &Backend,
+    text_bounds: Rectangle,
+    offset: Vector,
+) -> Vector
+where
+    Renderer: iced::advanced::text::Renderer,
+{
+    let height = line_height(renderer);
+    let (row, column) = backend.get_cursor_position();
+    let line = backend.display_line(row);
+    let cursor = Point::new(
+        measure_width(renderer, &line[..column]),
+        row as f32 * height,
+    );
+    let x = offset
+        .x
+        .min(cursor.x)
+        .max(cursor.x + CURSOR_WIDTH - text_bounds.width);
+    let y = offset
+        .y
+        .min(cursor.y)
+        .max(cursor.y + height - text_bounds.height);
+    Vector::new(x.max(0.0), y.max(0.0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    Vertical,
+    Horizontal,
+}
+
+struct Scrollbar {
+    axis: Axis,
+    track: Rectangle,
+    thumb: Rectangle,
+    // How far the content can scroll along the axis
+    range: f32,
+}
+
+impl Scrollbar {
+    /// Where the thumb starts and how long the track is, along the axis
+    fn extent(&self) -> (f32, f32, f32) {
+        match self.axis {
+            Axis::Vertical => (self.track.y, self.track.height, self.thumb.height),
+            Axis::Horizontal => (self.track.x, self.track.width, self.thumb.width),
+        }
+    }
+
+    /// How far along the thumb `point` is
+    fn grab(&self, point: Point) -> f32 {
+        match self.axis {
+            Axis::Vertical => point.y - self.thumb.y,
+            Axis::Horizontal => point.x - self.thumb.x,
         }
     }
+
+    /// The scroll offset that puts the thumb under `point`, held `grab`
+    /// pixels along from its start
+    fn offset_at(&self, point: Point, grab: f32) -> f32 {
+        let (track_start, track_length, thumb_length) = self.extent();
+        let along = match self.axis {
+            Axis::Vertical => point.y,
+            Axis::Horizontal => point.x,
+        };
+        let travel = (track_length - thumb_length).max(1.0);
+        ((along - grab - track_start) / travel).clamp(0.0, 1.0) * self.range
+    }
+}
+
+/// The scrollbars for any axis the content overflows on
+fn scrollbars(
+    bounds: Rectangle,
+    text_bounds: Rectangle,
+    content: Size,
+    offset: Vector,
+) -> Vec<Scrollbar> {
+    let mut scrollbars = Vec::new();
+    let range_y = content.height - text_bounds.height;
+    if range_y > 0.0 {
+        let track = Rectangle {
+            x: bounds.x + bounds.width - SCROLLBAR_WIDTH,
+            y: bounds.y,
+            width: SCROLLBAR_WIDTH,
+            height: bounds.height,
+        };
+        let length = (track.height * text_bounds.height / content.height)
+            .max(MIN_THUMB_LENGTH)
+            .min(track.height);
+        scrollbars.push(Scrollbar {
+            axis: Axis::Vertical,
+            track,
+            thumb: Rectangle {
+                y: track.y + (track.height - length) * (offset.y / range_y).min(1.0),
+                height: length,
+                ..track
+            },
+            range: range_y,
+        });
+    }
+    let range_x = content.width - text_bounds.width;
+    if range_x > 0.0 {
+        let track = Rectangle {
+            x: bounds.x,
+            y: bounds.y + bounds.height - SCROLLBAR_WIDTH,
+            // Leave the corner free for the vertical scrollbar
+            width: bounds.width - SCROLLBAR_WIDTH,
+            height: SCROLLBAR_WIDTH,
+        };
+        let length = (track.width * text_bounds.width / content.width)
+            .max(MIN_THUMB_LENGTH)
+            .min(track.width);
+        scrollbars.push(Scrollbar {
+            axis: Axis::Horizontal,
+            track,
+            thumb: Rectangle {
+                x: track.x + (track.width - length) * (offset.x / range_x).min(1.0),
+                width: length,
+                ..track
+            },
+            range: range_x,
+        });
+    }
+    scrollbars
+}
+
+/// The scroll offset with the `scrollbar`'s thumb dragged to `point`
+fn scroll_along(scrollbar: &Scrollbar, offset: Vector, point: Point, grab: f32) -> Vector {
+    let along = scrollbar.offset_at(point, grab);
+    match scrollbar.axis {
+        Axis::Vertical => Vector::new(offset.x, along),
+        Axis::Horizontal => Vector::new(along, offset.y),
+    }
 }
 
-pub fn text_editor(backend: &Mutex<Backend>) -> TextEditor {
+pub fn text_editor<Message>(backend: &Mutex<Backend>) -> TextEditor<'_, Message> {
     TextEditor::new(backend)
 }
 
+/// Focuses the text editor with the given id
+pub fn focus<Message: 'static>(id: widget::Id) -> Command<Message> {
+    Command::widget(widget::operation::focusable::focus(id))
+}
+
+/// A change to the state of text editors from outside of them
+enum StateOperation {
+    RunEmacs {
+        target: widget::Id,
+        command: emacs::Command,
+    },
+    Forget(Vec<widget::Id>),
+}
+
+impl<T> widget::Operation<T> for StateOperation {
+    fn container(
+        &mut self,
+        _id: Option<&widget::Id>,
+        _bounds: Rectangle,
+        operate_on_children: &mut dyn FnMut(&mut dyn widget::Operation<T>),
+    ) {
+        operate_on_children(self)
+    }
+
+    fn custom(&mut self, state: &mut dyn Any, id: Option<&widget::Id>) {
+        let Some(state) = state.downcast_mut::<State>() else {
+            return;
+        };
+        match self {
+            StateOperation::RunEmacs { target, command } => {
+                if id == Some(target) {
+                    state.queued = Some(*command);
+                }
+            }
+            StateOperation::Forget(ids) => {
+                for id in ids.iter() {
+                    state.views.remove(id);
+                }
+            }
+        }
+    }
+}
+
+/// Runs an Emacs `command` in the text editor with the given id, on the
+/// mark and kill ring it keeps
+pub fn run_emacs<Message: 'static>(id: widget::Id, command: emacs::Command) -> Command<Message> {
+    Command::widget(StateOperation::RunEmacs {
+        target: id,
+        command,
+    })
+}
+
+/// Drops what text editors keep of the buffers they showed with `ids`, for
+/// when those buffers are closed
+pub fn forget<Message: 'static>(ids: Vec<widget::Id>) -> Command<Message> {
+    Command::widget(StateOperation::Forget(ids))
+}
+
+/// Run an Emacs `command`, which yanks from and kills to the clipboard
+fn emacs_command(
+    emacs: &mut Emacs,
+    backend: &mut Backend,
+    command: emacs::Command,
+    clipboard: &mut dyn Clipboard,
+) {
+    let pasted = match command {
+        emacs::Command::Yank => clipboard.read(),
+        _ => None,
+    };
+    if let Some(killed) = emacs.command(backend, command, pasted) {
+        clipboard.write(killed);
+    }
+}
+
 const CURSOR_BLINK_INTERVAL_MILLIS: u128 = 500;
+const CURSOR_WIDTH: f32 = 2.0;
+const SCROLLBAR_WIDTH: f32 = 8.0;
+const MIN_THUMB_LENGTH: f32 = 20.0;
+const SCROLL_LINES_PER_NOTCH: f32 = 3.0;
+const GUTTER_PADDING: f32 = 8.0;
 
-pub struct TextEditor<'a> {
+/// How rows are numbered in the gutter
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbers {
+    /// No gutter
+    Hidden,
+    #[default]
+    Absolute,
+    /// Distance from the cursor's row, which shows its absolute number
+    Relative,
+}
+
+pub struct TextEditor<'a, Message> {
+    id: Option<widget::Id>,
     backend: &'a Mutex<Backend>,
+    view: ViewId,
     padding: Padding,
+    line_numbers: LineNumbers,
+    keymap: Keymap,
+    bindings: Option<&'a Bindings>,
+    vim: bool,
+    on_close_request: Option<Message>,
 }
 
-impl<'a> TextEditor<'a> {
+impl<'a, Message> TextEditor<'a, Message> {
     pub fn new(backend: &'a Mutex<Backend>) -> Self {
         Self {
+            id: None,
             backend,
+            view: ViewId::default(),
             padding: Padding::new(0.0),
+            line_numbers: LineNumbers::default(),
+            keymap: Keymap::default(),
+            bindings: None,
+            vim: false,
+            on_close_request: None,
         }
     }
 
+    /// Sets the id to focus the editor by. Giving each buffer its own id
+    /// keeps their scroll positions apart when they're switched between.
+    pub fn id(mut self, id: widget::Id) -> Self {
+        self.id = Some(id);
+        self
+    }
+
     pub fn padding<P: Into<Padding>>(mut self, padding: P) -> Self {
         self.padding = padding.into();
         self
     }
+
+    pub fn line_numbers(mut self, line_numbers: LineNumbers) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    pub fn keymap(mut self, keymap: Keymap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+
+    /// Sets bindings that take precedence over the keymap's own
+    pub fn bindings(mut self, bindings: &'a Bindings) -> Self {
+        self.bindings = Some(bindings);
+        self
+    }
+
+    /// Turns on Vim style modal editing
+    pub fn vim(mut self, vim: bool) -> Self {
+        self.vim = vim;
+        self
+    }
+
+    /// Sets which of the backend's views the editor shows, so editors
+    /// showing the same backend can each have cursors of their own
+    pub fn view(mut self, view: ViewId) -> Self {
+        self.view = view;
+        self
+    }
+
+    /// Sets the message produced when the window is asked to close, so the
+    /// application can decide what to do with unsaved changes
+    pub fn on_close_request(mut self, message: Message) -> Self {
+        self.on_close_request = Some(message);
+        self
+    }
+
+    /// Lock the backend, with the editor's view the current one
+    fn backend(&self) -> MutexGuard<'a, Backend> {
+        let mut backend = self.backend.lock().expect("Poisoned");
+        backend.set_view(self.view);
+        backend
+    }
+
+    /// The width of the line number gutter, which fits the largest row number
+    fn gutter_width<Renderer>(&self, renderer: &Renderer, backend: &Backend) -> f32
+    where
+        Renderer: iced::advanced::text::Renderer,
+    {
+        if self.line_numbers == LineNumbers::Hidden {
+            return 0.0;
+        }
+        let digits = backend.line_count().to_string().len();
+        measure_width(renderer, &"0".repeat(digits)) + 2.0 * GUTTER_PADDING
+    }
+
+    /// The area inside the padding and right of the gutter where text is drawn
+    fn text_bounds(&self, layout: Layout<'_>, gutter_width: f32) -> Rectangle {
+        let mut bounds = layout.bounds();
+        let half_p_w = self.padding.horizontal() / 2.0;
+        let half_p_h = self.padding.vertical() / 2.0;
+        bounds.x += half_p_w + gutter_width;
+        bounds.width -= self.padding.horizontal() + gutter_width;
+        bounds.y += half_p_h;
+        bounds.height -= self.padding.vertical();
+        bounds
+    }
 }
 
-impl<'a, Message, Renderer> Widget<Message, Renderer> for TextEditor<'a>
+impl<'a, Message, Renderer> Widget<Message, Renderer> for TextEditor<'a, Message>
 where
+    Message: Clone,
     Renderer: renderer::Renderer + iced::advanced::text::Renderer,
     Renderer::Theme: Stylesheet,
 {
@@ -107,7 +799,44 @@ where
     }
 
     fn state(&self) -> tree::State {
-        tree::State::new(State::default())
+        tree::State::new(State {
+            id: self.id.clone(),
+            ..State::default()
+        })
+    }
+
+    fn diff(&self, tree: &mut widget::Tree) {
+        let state = tree.state.downcast_mut::<State>();
+        if state.id != self.id {
+            state.switch_to(self.id.clone());
+        }
+        let mut backend = self.backend();
+        let selection = backend.selection();
+        if selection != state.selection {
+            state.selection = selection;
+            state.reveal_cursor = true;
+        }
+        match (self.vim, &state.vim) {
+            (true, None) => state.vim = Some(Vim::default()),
+            (false, Some(_)) => {
+                // Finish any change left open in insert mode
+                backend.end_change();
+                state.vim = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn operate(
+        &self,
+        tree: &mut widget::Tree,
+        _layout: Layout<'_>,
+        _renderer: &Renderer,
+        operation: &mut dyn widget::Operation<Message>,
+    ) {
+        let state = tree.state.downcast_mut::<State>();
+        operation.focusable(state, self.id.as_ref());
+        operation.custom(state, self.id.as_ref());
     }
 
     fn width(&self) -> iced::Length {
@@ -144,7 +873,7 @@ where
         tree: &iced::advanced::widget::Tree,
         renderer: &mut Renderer,
         theme: &Renderer::Theme,
-        style: &iced::advanced::renderer::Style,
+        _style: &iced::advanced::renderer::Style,
         layout: Layout<'_>,
         _cursor: mouse::Cursor,
         _viewport: &iced::Rectangle,
@@ -167,71 +896,251 @@ where
                 bg,
             );
         };
-        let mut bounds = layout.bounds();
-        let half_p_w = self.padding.horizontal() / 2.0;
-        let half_p_h = self.padding.vertical() / 2.0;
-        bounds.x += half_p_w;
-        bounds.width -= self.padding.horizontal();
-        bounds.y += half_p_h;
-        bounds.height -= self.padding.vertical();
-        // TODO reimplement my own text handling
-        let fake_node = Node::new(Size {
-            width: bounds.width,
-            height: bounds.height,
-        });
-        let text_layout = Layout::with_offset(Vector::new(bounds.x, bounds.y), &fake_node);
-        let backend = self.backend.lock().expect("Poisoned");
-        let content = backend.content();
-        let line_height = LineHeight::default();
-        draw_text(
-            renderer,
-            style,
-            text_layout,
-            &content,
-            None,
-            LineHeight::default(),
-            None,
-            TextAppearance {
-                color: Some(appearance.text_color),
-            },
-            alignment::Horizontal::Left,
-            alignment::Vertical::Top,
-            Shaping::Advanced,
-        );
-
-        // Draw cursor
-        let (cursor_row, cursor_column) = backend.get_cursor_position();
-        let text_size = renderer.default_size();
-        let height: f32 = line_height.to_absolute(Pixels::from(text_size)).into();
-        let y = height * cursor_row as f32;
-        let line = content.split('\n').nth(cursor_row).unwrap_or("");
-        let x = renderer.measure_width(
-            &line[0..cursor_column],
-            text_size,
-            renderer.default_font(),
-            Shaping::Advanced,
-        );
-        let width = 2.0f32;
-        if state.is_focused {
-            let is_cursor_visible =
-                ((state.now - state.focus_start).as_millis() / CURSOR_BLINK_INTERVAL_MILLIS) % 2
-                    == 0;
-            if is_cursor_visible {
-                renderer.fill_quad(
-                    renderer::Quad {
+        let mut backend = self.backend();
+        let gutter_width = self.gutter_width(renderer, &backend);
+        let bounds = self.text_bounds(layout, gutter_width);
+        let height = line_height(renderer);
+        let offset = if state.reveal_cursor {
+            // The next event will keep the cursor in view, but it might not come before drawing
+            let offset = scroll_to_cursor(renderer, &backend, bounds, state.scroll_offset);
+            clamp_scroll(renderer, &backend, bounds, offset)
+        } else {
+            clamp_scroll(renderer, &backend, bounds, state.scroll_offset)
+        };
+        // Only the rows in view are laid out and drawn
+        let rows = visible_rows(&backend, bounds, offset.y, height);
+        let visible = rows.clone();
+        let highlights = backend.highlights(rows.clone());
+        let matches = backend.search_matches(rows.clone());
+        let lines: Vec<(usize, String)> =
+            rows.map(|row| (row, backend.display_line(row))).collect();
+        let origin = Point::new(bounds.x - offset.x, bounds.y - offset.y);
+        let cursor_row = backend.selection().head.row;
+        let mut cursor_rows: Vec<usize> = backend
+            .selections()
+            .iter()
+            .map(|selection| selection.head.row)
+            .collect();
+        cursor_rows.dedup();
+
+        if self.line_numbers != LineNumbers::Hidden {
+            let layout_bounds = layout.bounds();
+            // Inside the border
+            fill_rectangle(
+                renderer,
+                Rectangle {
+                    x: layout_bounds.x + 1.0,
+                    y: layout_bounds.y + 1.0,
+                    width: bounds.x - layout_bounds.x - 1.0,
+                    height: layout_bounds.height - 2.0,
+                },
+                appearance.gutter_color,
+            );
+            let gutter = Rectangle {
+                x: bounds.x - gutter_width,
+                width: gutter_width,
+                ..bounds
+            };
+            renderer.with_layer(gutter, |renderer| {
+                for (row, _) in &lines {
+                    let y = origin.y + height * *row as f32;
+                    let (number, color) = if cursor_rows.contains(row) {
+                        fill_rectangle(
+                            renderer,
+                            Rectangle {
+                                y,
+                                height,
+                                ..gutter
+                            },
+                            appearance.active_line_color,
+                        );
+                        (row + 1, appearance.active_line_number_color)
+                    } else if self.line_numbers == LineNumbers::Relative {
+                        (row.abs_diff(cursor_row), appearance.line_number_color)
+                    } else {
+                        (row + 1, appearance.line_number_color)
+                    };
+                    renderer.fill_text(Text {
+                        content: &number.to_string(),
                         bounds: Rectangle {
-                            x: bounds.x + x,
-                            y: bounds.y + y,
-                            width,
+                            x: bounds.x - GUTTER_PADDING,
+                            y,
+                            width: gutter_width,
                             height,
                         },
-                        border_radius: 0.0.into(),
-                        border_width: 0.0f32,
-                        border_color: Color::TRANSPARENT,
+                        size: renderer.default_size(),
+                        line_height: LineHeight::default(),
+                        color,
+                        font: renderer.default_font(),
+                        horizontal_alignment: alignment::Horizontal::Right,
+                        vertical_alignment: alignment::Vertical::Top,
+                        shaping: Shaping::Basic,
+                    });
+                }
+            });
+        }
+
+        renderer.with_layer(bounds, |renderer| {
+            for row in &cursor_rows {
+                fill_rectangle(
+                    renderer,
+                    Rectangle {
+                        y: origin.y + height * *row as f32,
+                        height,
+                        ..bounds
                     },
-                    appearance.text_color,
+                    appearance.active_line_color,
                 );
             }
+
+            for ((row, line), found) in lines.iter().zip(&matches) {
+                for range in found {
+                    let x_start = measure_width(renderer, &line[..range.start]);
+                    let x_end = measure_width(renderer, &line[..range.end]);
+                    fill_rectangle(
+                        renderer,
+                        Rectangle {
+                            x: origin.x + x_start,
+                            y: origin.y + height * *row as f32,
+                            width: x_end - x_start,
+                            height,
+                        },
+                        appearance.match_color,
+                    );
+                }
+            }
+
+            // Draw selections behind the text
+            for selection in backend.selections() {
+                if selection.is_empty() {
+                    continue;
+                }
+                let (start, end) = (selection.start(), selection.end());
+                for (row, line) in lines
+                    .iter()
+                    .filter(|(row, _)| (start.row..=end.row).contains(row))
+                {
+                    let x_start = if *row == start.row {
+                        measure_width(renderer, &line[..backend.display_column(start)])
+                    } else {
+                        0.0
+                    };
+                    let x_end = if *row == end.row {
+                        measure_width(renderer, &line[..backend.display_column(end)])
+                    } else {
+                        // Leave room to show that the line break is selected
+                        measure_width(renderer, line) + measure_width(renderer, " ")
+                    };
+                    fill_rectangle(
+                        renderer,
+                        Rectangle {
+                            x: origin.x + x_start,
+                            y: origin.y + height * *row as f32,
+                            width: x_end - x_start,
+                            height,
+                        },
+                        appearance.selection_color,
+                    );
+                }
+            }
+
+            for ((row, line), spans) in lines.iter().zip(&highlights) {
+                for (range, color) in color_runs(line.len(), spans, &appearance) {
+                    renderer.fill_text(Text {
+                        content: &line[range.clone()],
+                        bounds: Rectangle {
+                            x: origin.x + measure_width(renderer, &line[..range.start]),
+                            y: origin.y + height * *row as f32,
+                            width: f32::INFINITY,
+                            height,
+                        },
+                        size: renderer.default_size(),
+                        line_height: LineHeight::default(),
+                        color,
+                        font: renderer.default_font(),
+                        horizontal_alignment: alignment::Horizontal::Left,
+                        vertical_alignment: alignment::Vertical::Top,
+                        shaping: Shaping::Advanced,
+                    });
+                }
+            }
+
+            // Draw cursors, which all blink together
+            let is_cursor_visible = state.is_focused
+                && ((state.now - state.focus_start).as_millis() / CURSOR_BLINK_INTERVAL_MILLIS)
+                    .is_multiple_of(2);
+            // Outside insert mode, Vim's cursor is a block over a character
+            let mode = state.vim.as_ref().map(Vim::mode);
+            let is_block = mode.is_some_and(|mode| mode != Mode::Insert);
+            if is_cursor_visible {
+                for selection in backend.selections() {
+                    let head = selection.head;
+                    if !visible.contains(&head.row) {
+                        continue;
+                    }
+                    let line = backend.display_line(head.row);
+                    let column = backend.display_column(head);
+                    let x = measure_width(renderer, &line[..column]);
+                    let (width, color) = if is_block {
+                        let under = line[column..].graphemes(true).next().unwrap_or(" ");
+                        let color = Color {
+                            a: 0.5,
+                            ..appearance.text_color
+                        };
+                        (measure_width(renderer, under), color)
+                    } else {
+                        (CURSOR_WIDTH, appearance.text_color)
+                    };
+                    fill_rectangle(
+                        renderer,
+                        Rectangle {
+                            x: origin.x + x,
+                            y: origin.y + height * head.row as f32,
+                            width,
+                            height,
+                        },
+                        color,
+                    );
+                }
+            }
+
+            // Show the chords of a sequence being typed, Vim's mode and the
+            // keys of a command, or an Emacs search, in the corner
+            let vim_label = state.vim.as_ref().map(|vim| {
+                let mode = match vim.mode() {
+                    Mode::Normal => "",
+                    Mode::Insert => "-- INSERT --",
+                    Mode::Visual => "-- VISUAL --",
+                    Mode::VisualLine => "-- VISUAL LINE --",
+                };
+                format!("{} {}", vim.pending(), mode)
+            });
+            let emacs_label = state.emacs.as_ref().and_then(Emacs::search_label);
+            let chords: Vec<String> = state.chords.iter().map(Chord::to_string).collect();
+            let chords_label = (!chords.is_empty()).then(|| chords.join(" "));
+            if let Some(label) = chords_label.or(vim_label).or(emacs_label) {
+                renderer.fill_text(Text {
+                    content: label.trim(),
+                    bounds: Rectangle {
+                        x: bounds.x + bounds.width - GUTTER_PADDING,
+                        y: bounds.y + bounds.height,
+                        width: bounds.width,
+                        height,
+                    },
+                    size: renderer.default_size(),
+                    line_height: LineHeight::default(),
+                    color: appearance.line_number_color,
+                    font: renderer.default_font(),
+                    horizontal_alignment: alignment::Horizontal::Right,
+                    vertical_alignment: alignment::Vertical::Bottom,
+                    shaping: Shaping::Basic,
+                });
+            }
+        });
+
+        let content = content_size(renderer, &backend, bounds, offset.y);
+        for scrollbar in scrollbars(layout.bounds(), bounds, content, offset) {
+            fill_rectangle(renderer, scrollbar.thumb, appearance.scrollbar_color);
         }
     }
 
@@ -241,13 +1150,25 @@ where
         event: Event,
         layout: Layout<'_>,
         cursor: mouse::Cursor,
-        _renderer: &Renderer,
-        _clipboard: &mut dyn Clipboard,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
         shell: &mut Shell<'_, Message>,
         _viewport: &iced::Rectangle,
     ) -> Status {
         let state = tree.state.downcast_mut::<State>();
-        let mut backend = self.backend.lock().expect("Poisoned");
+        let mut backend = self.backend();
+        let was_dirty = backend.is_dirty();
+        let text_bounds = self.text_bounds(layout, self.gutter_width(renderer, &backend));
+        // The window may have been resized since the offset was last set
+        let mut offset = clamp_scroll(renderer, &backend, text_bounds, state.scroll_offset);
+        let mut follow_cursor = std::mem::take(&mut state.reveal_cursor);
+        backend.set_page_rows((text_bounds.height / line_height(renderer)).floor() as usize);
+
+        if let Some(command) = state.queued.take() {
+            let emacs = state.emacs.get_or_insert_with(Emacs::default);
+            emacs_command(emacs, &mut backend, command, clipboard);
+            follow_cursor = true;
+        }
 
         let mut status = Status::Ignored;
         match event {
@@ -255,113 +1176,250 @@ where
                 key_code,
                 modifiers,
             }) => {
-                if state.is_focused {
-                    match key_code {
-                        KeyCode::Left => {
-                            backend.action(Action::Left);
-                            status = Status::Captured;
-                        }
-                        KeyCode::Right => {
-                            backend.action(Action::Right);
-                            status = Status::Captured;
-                        }
-                        KeyCode::Up => {
-                            backend.action(Action::Up);
-                            status = Status::Captured;
-                        }
-                        KeyCode::Down => {
-                            backend.action(Action::Down);
-                            status = Status::Captured;
-                        }
-                        KeyCode::PageUp => {
-                            backend.action(Action::PageUp);
-                            status = Status::Captured;
-                        }
-                        KeyCode::PageDown => {
-                            backend.action(Action::PageDown);
-                            status = Status::Captured;
-                        }
-                        KeyCode::Home => {
-                            backend.action(Action::Home);
-                            status = Status::Captured;
-                        }
-                        KeyCode::End => {
-                            backend.action(Action::End);
-                            status = Status::Captured;
+                // Vim gets the first look at the keys it uses
+                if let (true, Some(vim), Some(key)) = (
+                    state.is_focused,
+                    &mut state.vim,
+                    vim_key(key_code, modifiers),
+                ) {
+                    if vim.key(&mut backend, key) {
+                        status = Status::Captured;
+                        follow_cursor = true;
+                    }
+                }
+                let chord = Chord {
+                    key_code,
+                    modifiers,
+                };
+                let command =
+                    if !state.is_focused || status == Status::Captured || chord.is_modifier() {
+                        None
+                    } else {
+                        state.chords.push(chord);
+                        let no_bindings = Bindings::default();
+                        let bindings = self.bindings.unwrap_or(&no_bindings);
+                        match self.keymap.lookup(bindings, &state.chords) {
+                            Lookup::Command(command) => {
+                                state.chords.clear();
+                                Some(command)
+                            }
+                            Lookup::Prefix => {
+                                status = Status::Captured;
+                                None
+                            }
+                            Lookup::Unbound => {
+                                // A sequence that goes nowhere is swallowed whole
+                                if std::mem::take(&mut state.chords).len() > 1 {
+                                    status = Status::Captured;
+                                }
+                                None
+                            }
                         }
-                        KeyCode::Escape => {
-                            backend.action(Action::Escape);
-                            status = Status::Captured;
+                    };
+                if let Some(command) = command {
+                    let row = backend.selection().head.row;
+                    let paging = matches!(
+                        command,
+                        KeyCommand::Action(
+                            Action::PageUp
+                                | Action::PageDown
+                                | Action::SelectPageUp
+                                | Action::SelectPageDown
+                        )
+                    );
+                    match command {
+                        KeyCommand::Action(action) => match &mut state.emacs {
+                            Some(emacs) => emacs.action(&mut backend, action),
+                            None => backend.action(action),
+                        },
+                        KeyCommand::Copy => clipboard.write(backend.copy()),
+                        KeyCommand::Cut => clipboard.write(backend.cut()),
+                        KeyCommand::Paste => {
+                            if let Some(text) = clipboard.read() {
+                                backend.paste(&text);
+                            }
                         }
-                        KeyCode::Enter => {
-                            backend.action(Action::Enter);
-                            status = Status::Captured;
+                        KeyCommand::Emacs(command) => {
+                            let emacs = state.emacs.get_or_insert_with(Emacs::default);
+                            emacs_command(emacs, &mut backend, command, clipboard);
                         }
-                        KeyCode::Backspace => {
-                            backend.action(Action::Backspace);
-                            status = Status::Captured;
+                    }
+                    if paging {
+                        // Scroll along with the cursor so it keeps its place on screen
+                        let moved = backend.selection().head.row as f32 - row as f32;
+                        offset.y += moved * line_height(renderer);
+                    }
+                    status = Status::Captured;
+                }
+                if state.is_focused {
+                    follow_cursor = status == Status::Captured;
+                }
+            }
+            Event::Keyboard(KeyEvent::CharacterReceived(character)) => {
+                // Tabs are inserted by the Tab key press so they follow the indentation settings
+                // With Emacs bindings, Alt is Meta and doesn't type characters
+                let is_meta = self.keymap == Keymap::Emacs && state.modifiers.alt();
+                if state.is_focused && character != '\t' && !is_meta {
+                    match (&mut state.vim, &mut state.emacs) {
+                        (Some(vim), _) if !character.is_control() => {
+                            vim.key(&mut backend, Key::Char(character));
                         }
-                        KeyCode::Delete => {
-                            backend.action(Action::Delete);
-                            status = Status::Captured;
+                        (_, Some(emacs)) => emacs.action(&mut backend, Action::Insert(character)),
+                        _ => backend.action(Action::Insert(character)),
+                    }
+                    follow_cursor = true;
+                    status = Status::Captured;
+                }
+            }
+            Event::Keyboard(KeyEvent::ModifiersChanged(modifiers)) => {
+                state.modifiers = modifiers;
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                if let Some(point) = cursor.position_over(layout.bounds()) {
+                    let content = content_size(renderer, &backend, text_bounds, offset.y);
+                    let scrollbar = scrollbars(layout.bounds(), text_bounds, content, offset)
+                        .into_iter()
+                        .find(|scrollbar| scrollbar.track.contains(point));
+                    if let Some(scrollbar) = scrollbar {
+                        let grab = if scrollbar.thumb.contains(point) {
+                            scrollbar.grab(point)
+                        } else {
+                            // Clicking the track jumps the thumb's middle to the cursor
+                            scrollbar.extent().2 / 2.0
+                        };
+                        state.scrollbar_drag = Some((scrollbar.axis, grab));
+                        state.scroll_offset = scroll_along(&scrollbar, offset, point, grab);
+                    } else {
+                        let position = hit_test(renderer, &backend, text_bounds, point + offset);
+                        let click = mouse::Click::new(point, state.last_click);
+                        match click.kind() {
+                            click::Kind::Single if state.modifiers.alt() => {
+                                backend.add_cursor(position);
+                                state.is_dragging = true;
+                            }
+                            click::Kind::Single => {
+                                backend.set_cursor(position, state.modifiers.shift());
+                                state.is_dragging = true;
+                            }
+                            click::Kind::Double => backend.select_word(position),
+                            click::Kind::Triple => backend.select_line(position.row),
                         }
-                        _ => {}
+                        state.last_click = Some(click);
+                        state.is_focused = true;
                     }
+                    status = Status::Captured;
+                } else {
+                    // Clicking elsewhere, like on another input, takes the focus away
+                    state.is_focused = false;
                 }
             }
-            Event::Keyboard(KeyEvent::CharacterReceived(character)) => {
-                if state.is_focused {
-                    backend.action(Action::Insert(character));
+            Event::Mouse(mouse::Event::CursorMoved { position }) => {
+                if let Some((axis, grab)) = state.scrollbar_drag {
+                    let content = content_size(renderer, &backend, text_bounds, offset.y);
+                    if let Some(scrollbar) =
+                        scrollbars(layout.bounds(), text_bounds, content, offset)
+                            .into_iter()
+                            .find(|scrollbar| scrollbar.axis == axis)
+                    {
+                        state.scroll_offset = scroll_along(&scrollbar, offset, position, grab);
+                    }
+                    status = Status::Captured;
+                } else if state.is_dragging {
+                    let position = hit_test(renderer, &backend, text_bounds, position + offset);
+                    backend.set_cursor(position, true);
+                    follow_cursor = true;
                     status = Status::Captured;
                 }
             }
-            Event::Mouse(_event) => {}
-            Event::Window(event) => {
-                match event {
-                    WindowEvent::Resized {
-                        width: _width,
-                        height: _height,
-                    } => {
-                        // TODO: resizing
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                state.is_dragging = false;
+                state.scrollbar_drag = None;
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta })
+                if cursor.is_over(layout.bounds()) =>
+            {
+                let (x, y) = match delta {
+                    mouse::ScrollDelta::Lines { x, y } => {
+                        let lines = line_height(renderer) * SCROLL_LINES_PER_NOTCH;
+                        (x * lines, y * lines)
                     }
-                    WindowEvent::RedrawRequested(now) => {
-                        if state.is_focused {
-                            state.now = Instant::now();
+                    mouse::ScrollDelta::Pixels { x, y } => (x, y),
+                };
+                // Shift turns a plain mouse wheel sideways
+                let delta = if state.modifiers.shift() && x == 0.0 {
+                    Vector::new(y, 0.0)
+                } else {
+                    Vector::new(x, y)
+                };
+                state.scroll_offset = clamp_scroll(renderer, &backend, text_bounds, offset - delta);
+                status = Status::Captured;
+            }
+            Event::Window(event) => match event {
+                WindowEvent::RedrawRequested(now) if state.is_focused => {
+                    state.now = Instant::now();
 
-                            let millis_until_redraw = CURSOR_BLINK_INTERVAL_MILLIS
-                                - (now - state.focus_start).as_millis()
-                                    % CURSOR_BLINK_INTERVAL_MILLIS;
+                    let millis_until_redraw = CURSOR_BLINK_INTERVAL_MILLIS
+                        - (now - state.focus_start).as_millis() % CURSOR_BLINK_INTERVAL_MILLIS;
 
-                            shell.request_redraw(window::RedrawRequest::At(
-                                now + Duration::from_millis(millis_until_redraw as u64),
-                            ));
-                        }
-                    }
-                    WindowEvent::CloseRequested => todo!(),
-                    WindowEvent::Focused => {
-                        state.is_focused = true;
-                        state.focus_start = Instant::now();
-                        shell.request_redraw(window::RedrawRequest::NextFrame);
+                    shell.request_redraw(window::RedrawRequest::At(
+                        now + Duration::from_millis(millis_until_redraw as u64),
+                    ));
+                }
+                WindowEvent::CloseRequested => {
+                    if let Some(on_close_request) = &self.on_close_request {
+                        shell.publish(on_close_request.clone());
+                        status = Status::Captured;
                     }
-                    WindowEvent::Unfocused => state.is_focused = false,
-                    _ => {}
                 }
-            }
+                WindowEvent::Focused if state.was_focused => {
+                    state.is_focused = true;
+                    state.focus_start = Instant::now();
+                    shell.request_redraw(window::RedrawRequest::NextFrame);
+                }
+                WindowEvent::Unfocused => {
+                    state.was_focused = std::mem::take(&mut state.is_focused);
+                }
+                _ => {}
+            },
             _ => {}
         }
         if status == Status::Captured && state.is_focused {
             state.focus_start = Instant::now();
         }
+        if follow_cursor {
+            let offset = scroll_to_cursor(renderer, &backend, text_bounds, offset);
+            state.scroll_offset = clamp_scroll(renderer, &backend, text_bounds, offset);
+        }
+        state.selection = backend.selection();
+        if backend.is_dirty() != was_dirty {
+            // Rebuild the application so the title shows the modified marker
+            shell.invalidate_widgets();
+        }
         status
     }
 }
 
-impl<'a, Message, Renderer> From<TextEditor<'a>> for Element<'a, Message, Renderer>
+/// The key Vim is given for a key press, if it takes that key from the
+/// editor's own bindings. Characters arrive separately.
+fn vim_key(key_code: KeyCode, modifiers: Modifiers) -> Option<Key> {
+    match key_code {
+        KeyCode::Escape => Some(Key::Escape),
+        KeyCode::Enter => Some(Key::Enter),
+        KeyCode::Backspace if !modifiers.control() => Some(Key::Backspace),
+        KeyCode::Delete if !modifiers.control() => Some(Key::Delete),
+        KeyCode::Tab if !modifiers.shift() && !modifiers.control() => Some(Key::Tab),
+        KeyCode::R if modifiers.control() => Some(Key::Ctrl('r')),
+        _ => None,
+    }
+}
+
+impl<'a, Message, Renderer> From<TextEditor<'a, Message>> for Element<'a, Message, Renderer>
 where
+    Message: 'a + Clone,
     Renderer: renderer::Renderer + iced::advanced::text::Renderer,
     Renderer::Theme: Stylesheet,
 {
-    fn from(text_editor: TextEditor<'a>) -> Self {
+    fn from(text_editor: TextEditor<'a, Message>) -> Self {
         Self::new(text_editor)
     }
 }
@@ -1,3 +1,36 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use ropey::Rope;
+use unicode_segmentation::UnicodeSegmentation;
+
+mod cursors;
+use cursors::Cursors;
+
+mod highlight;
+use highlight::Highlighter;
+pub use highlight::{Highlight, Language, Span};
+
+mod history;
+use history::{Edit, History};
+
+mod indentation;
+pub use indentation::Indentation;
+
+mod search;
+use search::Search;
+pub use search::SearchQuery;
+
+mod syntax;
+use syntax::Syntax;
+
+mod words;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Action {
     Insert(char),
     Delete,
@@ -12,27 +45,288 @@ pub enum Action {
     Escape,
     Enter,
     Backspace,
+    Undo,
+    Redo,
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    SelectHome,
+    SelectEnd,
+    SelectPageUp,
+    SelectPageDown,
+    SelectAll,
+    DocumentStart,
+    DocumentEnd,
+    SelectDocumentStart,
+    SelectDocumentEnd,
+    Indent,
+    Dedent,
+    ExpandSelection,
+    ShrinkSelection,
+    MatchingBracket,
+    FindNext,
+    FindPrevious,
+    AddCursorUp,
+    AddCursorDown,
+    AddNextOccurrence,
+    WordLeft,
+    WordRight,
+    SelectWordLeft,
+    SelectWordRight,
+    SubwordLeft,
+    SubwordRight,
+    SelectSubwordLeft,
+    SelectSubwordRight,
+    DeleteWordBackward,
+    DeleteWordForward,
+    DeleteSubwordBackward,
+    DeleteSubwordForward,
+}
+
+/// The ways the cursor can move, with or without extending the selection
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    DocumentStart,
+    DocumentEnd,
+    WordLeft,
+    WordRight,
+    SubwordLeft,
+    SubwordRight,
+}
+
+/// A place in the document, where `column` counts grapheme clusters into
+/// the row, so a position can never split a character
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub row: usize,
+    pub column: usize,
+}
+
+/// A range of text between the `anchor`, where the selection was started,
+/// and the `head`, where the cursor is. The cursor on its own is an empty
+/// selection.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub anchor: Position,
+    pub head: Position,
+}
+
+impl Selection {
+    pub fn cursor(position: Position) -> Self {
+        Self {
+            anchor: position,
+            head: position,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    /// Whichever end of the selection comes first in the document
+    pub fn start(&self) -> Position {
+        self.anchor.min(self.head)
+    }
+
+    /// Whichever end of the selection comes last in the document
+    pub fn end(&self) -> Position {
+        self.anchor.max(self.head)
+    }
+}
+
+/// Names one of the places a backend is shown, like a pane. Each view has
+/// cursors of its own, so the text can be edited in two places at once.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ViewId(pub usize);
+
+/// What's kept of a view while another is current
+#[derive(Debug, Default)]
+struct View {
+    cursors: Cursors,
+    typing: bool,
+    expansions: Vec<(Cursors, Cursors)>,
 }
 
 #[derive(Debug)]
 pub struct Backend {
-    content: Vec<String>, // A vector of rows
-    cursor_row: usize,
-    cursor_column: usize,
+    content: Rope,
+    cursors: Cursors, // Those of `view`
+    view: ViewId,
+    // The other views, whose cursors edits move along with the text
+    views: HashMap<ViewId, View>,
+    history: History,
+    typing: bool, // Whether the last action typed a character
+    path: Option<PathBuf>,
+    crlf: bool, // Whether the file on disk uses "\r\n" line breaks
+    // The last whole line that was copied, so pasting it can insert a line
+    line_clipboard: Option<String>,
+    page_rows: usize, // How many rows the page motions move by
+    indentation: Indentation,
+    highlighter: Highlighter,
+    syntax: Option<Syntax>,
+    // The selections before and after each expansion, so they can be shrunk back
+    expansions: Vec<(Cursors, Cursors)>,
+    search: Option<Search>,
+    // The length of the undo history when the open change began
+    change_start: Option<usize>,
 }
 
 impl Default for Backend {
     fn default() -> Self {
         Self {
-            content: vec![String::new()],
-            cursor_row: Default::default(),
-            cursor_column: Default::default(),
+            content: Rope::new(),
+            cursors: Cursors::default(),
+            view: ViewId::default(),
+            views: HashMap::new(),
+            history: Default::default(),
+            typing: false,
+            path: None,
+            crlf: false,
+            line_clipboard: None,
+            page_rows: 1,
+            indentation: Indentation::default(),
+            highlighter: Highlighter::default(),
+            syntax: None,
+            expansions: Vec::new(),
+            search: None,
+            change_start: None,
+        }
+    }
+}
+
+impl From<&str> for Backend {
+    fn from(text: &str) -> Self {
+        Self {
+            content: Rope::from_str(text),
+            ..Default::default()
         }
     }
 }
 
 impl Backend {
+    /// Load the file at `path` into a new backend
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let crlf = text.contains("\r\n");
+        let content = if crlf {
+            Rope::from_str(&text.replace("\r\n", "\n"))
+        } else {
+            Rope::from_str(&text)
+        };
+        let mut backend = Self {
+            content,
+            path: Some(path.to_path_buf()),
+            crlf,
+            ..Default::default()
+        };
+        backend.set_language(Language::from_path(path));
+        Ok(backend)
+    }
+
+    /// Create an empty backend that will be saved to `path`
+    pub fn with_path<P: AsRef<Path>>(path: P) -> Self {
+        let mut backend = Self {
+            path: Some(path.as_ref().to_path_buf()),
+            ..Default::default()
+        };
+        backend.set_language(Language::from_path(path.as_ref()));
+        backend
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Whether there's an edit for `Action::Undo` to take back
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    /// Whether there's an undone edit for `Action::Redo` to make again
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    /// Whether there are changes that have not been saved
+    pub fn is_dirty(&self) -> bool {
+        !self.history.is_saved()
+    }
+
+    /// Write the content back to the backend's path.
+    ///
+    /// The content is written to a temporary file next to the destination
+    /// first, then renamed over it, so a failed save never leaves a
+    /// half-written file behind.
+    pub fn save(&mut self) -> io::Result<()> {
+        let path = self
+            .path
+            .clone()
+            .ok_or_else(|| io::Error::other("The buffer has no file path"))?;
+        self.replace_file(&path)?;
+        self.history.mark_saved();
+        Ok(())
+    }
+
+    /// Save to `path`, which becomes the backend's path if that worked
+    pub fn save_as<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.replace_file(path.as_ref())?;
+        self.set_path(path);
+        self.history.mark_saved();
+        Ok(())
+    }
+
+    fn replace_file(&self, path: &Path) -> io::Result<()> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid file path"))?;
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+
+        let result = self.write_to(&temp_path).and_then(|()| {
+            if let Ok(metadata) = fs::metadata(path) {
+                fs::set_permissions(&temp_path, metadata.permissions())?;
+            }
+            fs::rename(&temp_path, path)
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    /// Set a new path without saving, like after the file was moved
+    pub fn set_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.path = Some(path.as_ref().to_path_buf());
+        self.set_language(Language::from_path(path.as_ref()));
+    }
+
+    fn write_to(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for chunk in self.content.chunks() {
+            if self.crlf {
+                writer.write_all(chunk.replace('\n', "\r\n").as_bytes())?;
+            } else {
+                writer.write_all(chunk.as_bytes())?;
+            }
+        }
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+    }
+
+    /// Apply `action` at every cursor
     pub fn action(&mut self, action: Action) {
+        let continues_typing = std::mem::take(&mut self.typing);
         match action {
             Action::Insert(c) => {
                 if c.is_control() && !['\t', '\n', '\u{92}'].contains(&c) {
@@ -40,147 +334,1031 @@ impl Backend {
                 } else if c == '\n' {
                     self.action(Action::Enter);
                 } else {
-                    self.content
-                        .get_mut(self.cursor_row)
-                        .expect("Cursor went beyond available rows!")
-                        .insert(self.cursor_column, c);
-                    self.cursor_column += 1;
-                }
-            }
-            Action::Delete => {
-                // TODO: Clean up this
-                if self.cursor_column
-                    != self
-                        .content
-                        .get(self.cursor_row)
-                        .expect("Cursor went beyond available rows!")
-                        .len()
-                {
-                    let line = self
-                        .content
-                        .get_mut(self.cursor_row)
-                        .expect("Cursor went beyond available rows!");
-                    line.remove(self.cursor_column);
-                } else if self.cursor_row < self.content.len().saturating_sub(1) {
-                    let source_line = self.content.remove(self.cursor_row);
-                    self.content
-                        .get_mut(self.cursor_row)
-                        .expect("Cursor went beyond available rows!")
-                        .push_str(&source_line);
-                }
-            }
-            Action::Up => {
-                self.cursor_row = self.cursor_row.saturating_sub(1);
-                self.cursor_column = self.cursor_column.min(
-                    self.content
-                        .get(self.cursor_row)
-                        .expect("Cursor went beyond available rows!")
-                        .len(),
-                )
+                    let text = c.to_string();
+                    self.replace_selections(continues_typing, |_, _, range| {
+                        Some((range, text.clone()))
+                    });
+                    self.typing = true;
+                }
             }
-            Action::Down => {
-                self.cursor_row = self
-                    .cursor_row
-                    .saturating_add(1)
-                    .min(self.content.len().saturating_sub(1));
-                self.cursor_column = self.cursor_column.min(
-                    self.content
-                        .get(self.cursor_row)
-                        .expect("Cursor went beyond available rows!")
-                        .len(),
-                )
+            // Deleting at the end of a row joins the next row onto it, which
+            // is just removing the line break
+            Action::Delete => self.delete(Motion::Right),
+            Action::DeleteWordForward => self.delete(Motion::WordRight),
+            Action::DeleteSubwordForward => self.delete(Motion::SubwordRight),
+            Action::Up => self.move_cursor(Motion::Up, false),
+            Action::Down => self.move_cursor(Motion::Down, false),
+            Action::Left => self.move_cursor(Motion::Left, false),
+            Action::Right => self.move_cursor(Motion::Right, false),
+            Action::Home => self.move_cursor(Motion::Home, false),
+            Action::End => self.move_cursor(Motion::End, false),
+            Action::PageUp => self.move_cursor(Motion::PageUp, false),
+            Action::PageDown => self.move_cursor(Motion::PageDown, false),
+            Action::SelectUp => self.move_cursor(Motion::Up, true),
+            Action::SelectDown => self.move_cursor(Motion::Down, true),
+            Action::SelectLeft => self.move_cursor(Motion::Left, true),
+            Action::SelectRight => self.move_cursor(Motion::Right, true),
+            Action::SelectHome => self.move_cursor(Motion::Home, true),
+            Action::SelectEnd => self.move_cursor(Motion::End, true),
+            Action::SelectPageUp => self.move_cursor(Motion::PageUp, true),
+            Action::SelectPageDown => self.move_cursor(Motion::PageDown, true),
+            Action::DocumentStart => self.move_cursor(Motion::DocumentStart, false),
+            Action::DocumentEnd => self.move_cursor(Motion::DocumentEnd, false),
+            Action::SelectDocumentStart => self.move_cursor(Motion::DocumentStart, true),
+            Action::SelectDocumentEnd => self.move_cursor(Motion::DocumentEnd, true),
+            Action::WordLeft => self.move_cursor(Motion::WordLeft, false),
+            Action::WordRight => self.move_cursor(Motion::WordRight, false),
+            Action::SelectWordLeft => self.move_cursor(Motion::WordLeft, true),
+            Action::SelectWordRight => self.move_cursor(Motion::WordRight, true),
+            Action::SubwordLeft => self.move_cursor(Motion::SubwordLeft, false),
+            Action::SubwordRight => self.move_cursor(Motion::SubwordRight, false),
+            Action::SelectSubwordLeft => self.move_cursor(Motion::SubwordLeft, true),
+            Action::SelectSubwordRight => self.move_cursor(Motion::SubwordRight, true),
+            Action::Indent => {
+                let single_rows = self
+                    .cursors
+                    .all()
+                    .iter()
+                    .all(|selection| selection.start().row == selection.end().row);
+                if single_rows {
+                    self.replace_selections(false, |backend, selection, range| {
+                        let head = selection.start();
+                        let line = backend.line(head.row);
+                        let column = backend
+                            .indentation
+                            .expand_tabs(&line[..backend.byte_column(&line, head.column)])
+                            .chars()
+                            .count();
+                        Some((range, backend.indentation.indent_from(column)))
+                    });
+                } else {
+                    self.indent_lines(false);
+                }
             }
-            Action::Left => self.cursor_column = self.cursor_column.saturating_sub(1),
-            Action::Right => {
-                let line_length = self
-                    .content
-                    .get(self.cursor_row)
-                    .expect("Cursor went beyond available rows!")
-                    .len();
-                self.cursor_column = self.cursor_column.saturating_add(1);
-                if self.cursor_column > line_length {
-                    self.cursor_column = 0;
-                    self.cursor_row = self
-                        .cursor_row
-                        .saturating_add(1)
-                        .min(self.content.len().saturating_sub(1));
-                }
-            }
-            Action::Home => self.cursor_column = 0,
-            Action::End => {
-                let text = self
-                    .content
-                    .get(self.cursor_row)
-                    .expect("Cursor went beyond available rows!");
-                self.cursor_column = text.len();
-            }
-            Action::PageUp => {
-                self.cursor_row = 0;
-                self.cursor_column = 0
-            }
-            Action::PageDown => {
-                self.cursor_row = self.content.len().saturating_sub(1);
-                self.cursor_column = self
-                    .content
-                    .get(self.cursor_row)
-                    .expect("Cursor went beyond available rows!")
-                    .len();
-            }
-            Action::Escape => todo!(),
-            Action::Enter => {
-                let end_of_line = self
-                    .content
-                    .get_mut(self.cursor_row)
-                    .expect("Cursor went beyond available rows!")
-                    .split_off(self.cursor_column);
-                self.content
-                    .insert(self.cursor_row.saturating_add(1), end_of_line);
-                self.cursor_row += 1;
-                self.cursor_column = 0;
-            }
-            Action::Backspace => {
-                if self.cursor_column == 0 {
-                    if self.cursor_row != 0 {
-                        let removed_line = self.content.remove(self.cursor_row);
-                        self.cursor_row = self.cursor_row.saturating_sub(1);
-                        let destination_line = self
-                            .content
-                            .get_mut(self.cursor_row)
-                            .expect("Cursor went beyond available rows!");
-                        self.cursor_column = destination_line.len();
-                        destination_line.push_str(&removed_line);
+            Action::Dedent => self.indent_lines(true),
+            Action::ExpandSelection => {
+                let ranges: Vec<Range<usize>> = self
+                    .cursors
+                    .all()
+                    .iter()
+                    .map(|selection| {
+                        let range = self.range_of(*selection);
+                        self.content.char_to_byte(range.start)..self.content.char_to_byte(range.end)
+                    })
+                    .collect();
+                let Some(syntax) = &mut self.syntax else {
+                    return;
+                };
+                let mut expanded = ranges
+                    .into_iter()
+                    .map(|bytes| syntax.expand(&self.content, bytes))
+                    .collect::<Vec<_>>()
+                    .into_iter();
+                let before = self.cursors.clone();
+                self.map_selections(|backend, selection| match expanded.next().flatten() {
+                    Some(bytes) => Selection {
+                        anchor: backend.position_of(backend.content.byte_to_char(bytes.start)),
+                        head: backend.position_of(backend.content.byte_to_char(bytes.end)),
+                    },
+                    None => selection,
+                });
+                if self.cursors != before {
+                    self.expansions.push((before, self.cursors.clone()));
+                }
+            }
+            Action::ShrinkSelection => match self.expansions.pop() {
+                Some((before, after)) if after == self.cursors => self.cursors = before,
+                // The selection was changed some other way since it was expanded
+                _ => self.expansions.clear(),
+            },
+            Action::MatchingBracket => {
+                let cursors: Vec<usize> = self
+                    .cursors
+                    .all()
+                    .iter()
+                    .map(|selection| self.char_index(selection.head))
+                    .collect();
+                let Some(syntax) = &mut self.syntax else {
+                    return;
+                };
+                let mut matching = cursors
+                    .into_iter()
+                    .map(|cursor| {
+                        // Look for a bracket after the cursor, then before it
+                        [Some(cursor), cursor.checked_sub(1)]
+                            .into_iter()
+                            .flatten()
+                            .find_map(|char| {
+                                let byte = self.content.char_to_byte(char);
+                                syntax.matching_bracket(&self.content, byte)
+                            })
+                    })
+                    .collect::<Vec<_>>()
+                    .into_iter();
+                self.map_selections(|backend, selection| match matching.next().flatten() {
+                    Some(byte) => {
+                        Selection::cursor(backend.position_of(backend.content.byte_to_char(byte)))
                     }
+                    None => selection,
+                });
+            }
+            Action::SelectAll => {
+                self.cursors = Cursors::new(Selection {
+                    anchor: Position::default(),
+                    head: self.position_of(self.content.len_chars()),
+                });
+            }
+            Action::FindNext => {
+                let range = self.range_of(self.cursors.primary());
+                self.select_match(|matches| {
+                    let next = matches.partition_point(|found| found.start < range.end);
+                    next % matches.len()
+                });
+            }
+            Action::FindPrevious => {
+                let range = self.range_of(self.cursors.primary());
+                self.select_match(|matches| {
+                    let previous = matches.partition_point(|found| found.start < range.start);
+                    previous.checked_sub(1).unwrap_or(matches.len() - 1)
+                });
+            }
+            Action::AddCursorUp | Action::AddCursorDown => {
+                let (edge, motion) = match action {
+                    Action::AddCursorUp => (self.cursors.all()[0], Motion::Up),
+                    _ => (self.cursors.all()[self.cursors.len() - 1], Motion::Down),
+                };
+                let head = self.motion_target(edge.head, motion);
+                self.cursors.add(Selection::cursor(head));
+            }
+            Action::AddNextOccurrence => {
+                let primary = self.cursors.primary();
+                if primary.is_empty() {
+                    // Start with the word at the cursor
+                    let word = self.word_at(primary.head);
+                    self.cursors.set_primary(word);
+                } else if let Some(found) = self.next_occurrence(primary) {
+                    self.cursors.add(Selection {
+                        anchor: self.position_of(found.start),
+                        head: self.position_of(found.end),
+                    });
+                }
+            }
+            Action::Escape => {
+                // Drop the other cursors, or the selection if there's just one
+                let primary = self.cursors.primary();
+                self.cursors = if self.cursors.len() > 1 {
+                    Cursors::new(primary)
                 } else {
-                    let line = self
-                        .content
-                        .get_mut(self.cursor_row)
-                        .expect("Cursor went beyong available rows!");
-                    self.cursor_column = self.cursor_column.saturating_sub(1);
-                    line.remove(self.cursor_column);
+                    Cursors::new(Selection::cursor(primary.head))
+                };
+            }
+            Action::Enter => {
+                self.replace_selections(false, |_, _, range| Some((range, "\n".to_string())));
+            }
+            // At the start of a row this removes the previous line break
+            Action::Backspace => self.delete(Motion::Left),
+            Action::DeleteWordBackward => self.delete(Motion::WordLeft),
+            Action::DeleteSubwordBackward => self.delete(Motion::SubwordLeft),
+            Action::Undo => {
+                if let Some(transaction) = self.history.undo() {
+                    let edits = transaction.edits.clone();
+                    self.cursors = transaction.cursors_before.clone();
+                    for edit in edits.iter().rev() {
+                        edit.revert(&mut self.content);
+                        self.edited(edit.position, &edit.inserted, &edit.deleted);
+                    }
                 }
             }
+            Action::Redo => {
+                if let Some(transaction) = self.history.redo() {
+                    let edits = transaction.edits.clone();
+                    self.cursors = transaction.cursors_after.clone();
+                    for edit in edits.iter() {
+                        edit.apply(&mut self.content);
+                        self.edited(edit.position, &edit.deleted, &edit.inserted);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The selected text, one line per cursor, or the cursors' whole lines
+    /// if nothing is selected
+    pub fn copy(&mut self) -> String {
+        let selections = self.cursors.all();
+        if selections.iter().all(Selection::is_empty) {
+            let mut rows: Vec<usize> = selections.iter().map(|cursor| cursor.head.row).collect();
+            rows.dedup();
+            let lines: String = rows
+                .into_iter()
+                .map(|row| self.line_with_break(row))
+                .collect();
+            self.line_clipboard = Some(lines.clone());
+            lines
+        } else {
+            let text = selections
+                .iter()
+                .map(|selection| self.content.slice(self.range_of(*selection)).to_string())
+                .collect::<Vec<String>>()
+                .join("\n");
+            self.line_clipboard = None;
+            text
+        }
+    }
+
+    /// Remove and return the selected text, or the cursors' whole lines if
+    /// nothing is selected
+    pub fn cut(&mut self) -> String {
+        let text = self.copy();
+        let whole_lines = self.line_clipboard.is_some();
+        self.replace_selections(false, |backend, selection, range| {
+            if !whole_lines {
+                return Some((range, String::new()));
+            }
+            let row = selection.head.row;
+            let line_start = backend.content.line_to_char(row);
+            let range = if row + 1 < backend.line_count() {
+                line_start..backend.content.line_to_char(row + 1)
+            } else if row > 0 {
+                // The last line has no line break of its own, so take the
+                // previous one instead
+                line_start - 1..line_start + backend.line_char_length(row)
+            } else {
+                line_start..line_start + backend.line_char_length(row)
+            };
+            Some((range, String::new()))
+        });
+        text
+    }
+
+    /// Replace the selections with `text`, giving each cursor one line of it
+    /// if it has a line per cursor. Whole lines that were copied without a
+    /// selection are pasted as new lines above the cursors.
+    pub fn paste(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n");
+        let whole_lines = self.line_clipboard.as_deref() == Some(text.as_str())
+            && self.cursors.all().iter().all(Selection::is_empty);
+        let lines: Vec<&str> = if whole_lines {
+            text.split_inclusive('\n').collect()
+        } else {
+            text.split('\n').collect()
+        };
+        let distribute = self.cursors.len() > 1 && lines.len() == self.cursors.len();
+        let mut lines = lines.into_iter();
+        self.replace_selections(false, |backend, selection, range| {
+            let text = if distribute {
+                lines.next().unwrap_or_default()
+            } else {
+                &text
+            };
+            if whole_lines {
+                // The cursor keeps its place in its line, which moves down
+                let line_start = backend.content.line_to_char(selection.head.row);
+                Some((line_start..line_start, text.to_string()))
+            } else {
+                Some((range, text.to_string()))
+            }
+        });
+    }
+
+    /// Replace the selections with `text`
+    pub fn insert(&mut self, text: &str) {
+        self.replace_selections(false, |_, _, range| Some((range, text.to_string())));
+    }
+
+    /// The text from `start` up to `end`
+    pub fn text_between(&self, start: Position, end: Position) -> String {
+        let range = self.range_of(Selection {
+            anchor: start,
+            head: end,
+        });
+        self.content.slice(range).to_string()
+    }
+
+    /// Start collecting edits into a single undo step, until `end_change`
+    pub fn begin_change(&mut self) {
+        self.change_start.get_or_insert(self.history.len());
+    }
+
+    /// Turn the edits since `begin_change` into a single undo step
+    pub fn end_change(&mut self) {
+        if let Some(start) = self.change_start.take() {
+            self.history.squash(start);
         }
     }
 
+    /// The whole document as one string, for tests and debugging
     pub fn content(&self) -> String {
-        // TODO: find a better way to render the text
-        // TODO: Configurable tab width
-        // TODO: Configurable option to insert spaces instead of tabs
-        self.content
-            .iter()
-            .map(|s| s.replace('\t', "    "))
-            .collect::<Vec<String>>()
-            .join("\n")
+        self.content.to_string()
+    }
+
+    pub fn indentation(&self) -> Indentation {
+        self.indentation
+    }
+
+    pub fn set_indentation(&mut self, indentation: Indentation) {
+        self.indentation = Indentation {
+            tab_width: indentation.tab_width.max(1),
+            ..indentation
+        };
+    }
+
+    /// The number of rows in the document. An empty document has one row.
+    pub fn line_count(&self) -> usize {
+        self.content.len_lines()
     }
 
+    /// The text of a single row, without its line break.
+    pub fn line(&self, row: usize) -> Cow<'_, str> {
+        let line = self.content.line(row);
+        let length = self.line_char_length(row);
+        line.slice(..length).into()
+    }
+
+    /// The row and display column of the primary cursor
     pub fn get_cursor_position(&self) -> (usize, usize) {
-        // TODO: Fix this dirty hack to deal with my auto-replacement
-        // of tabs with spaces
-        let line = self
-            .content
-            .get(self.cursor_row)
-            .expect("Cursow went beyond available rows!");
-        let fixed_column = line[0..self.cursor_column].replace('\t', "    ").len();
-        (self.cursor_row, fixed_column)
+        let head = self.cursors.primary().head;
+        (head.row, self.display_column(head))
+    }
+
+    /// The selection of the primary cursor, which is the one added last
+    pub fn selection(&self) -> Selection {
+        self.cursors.primary()
+    }
+
+    /// Every cursor's selection, in document order
+    pub fn selections(&self) -> &[Selection] {
+        self.cursors.all()
+    }
+
+    /// Make `view` the one whose cursors actions move and edit at. A view
+    /// that hasn't been used before starts with the current one's cursors.
+    pub fn set_view(&mut self, view: ViewId) {
+        if view == self.view {
+            return;
+        }
+        let next = self.views.remove(&view).unwrap_or_else(|| View {
+            cursors: self.cursors.clone(),
+            ..View::default()
+        });
+        let previous = View {
+            cursors: std::mem::replace(&mut self.cursors, next.cursors),
+            typing: std::mem::replace(&mut self.typing, next.typing),
+            expansions: std::mem::replace(&mut self.expansions, next.expansions),
+        };
+        self.views
+            .insert(std::mem::replace(&mut self.view, view), previous);
+    }
+
+    /// Forget the cursors of `view`, which isn't shown anymore. The current
+    /// view is always kept.
+    pub fn remove_view(&mut self, view: ViewId) {
+        self.views.remove(&view);
+    }
+
+    /// Set how many rows `PageUp` and `PageDown` move, which is how many
+    /// rows fit in the view
+    pub fn set_page_rows(&mut self, rows: usize) {
+        self.page_rows = rows.max(1);
+    }
+
+    /// Move the cursor to `position`, leaving it as the only cursor, or
+    /// extend the primary selection to it if `extend` is set
+    pub fn set_cursor(&mut self, position: Position, extend: bool) {
+        self.typing = false;
+        let head = self.clamp(position);
+        if extend {
+            let anchor = self.cursors.primary().anchor;
+            self.cursors.set_primary(Selection { anchor, head });
+        } else {
+            self.cursors = Cursors::new(Selection::cursor(head));
+        }
+    }
+
+    /// Add a cursor at `position`, which becomes the primary one
+    pub fn add_cursor(&mut self, position: Position) {
+        self.typing = false;
+        let head = self.clamp(position);
+        self.cursors.add(Selection::cursor(head));
+    }
+
+    /// Select the word at `position`, using Unicode word boundaries
+    pub fn select_word(&mut self, position: Position) {
+        self.typing = false;
+        self.cursors = Cursors::new(self.word_at(position));
+    }
+
+    /// Select a whole row, including its line break
+    pub fn select_line(&mut self, row: usize) {
+        self.typing = false;
+        let row = row.min(self.line_count().saturating_sub(1));
+        let head = if row + 1 < self.line_count() {
+            Position {
+                row: row + 1,
+                column: 0,
+            }
+        } else {
+            Position {
+                row,
+                column: self.line_length(row),
+            }
+        };
+        self.cursors = Cursors::new(Selection {
+            anchor: Position { row, column: 0 },
+            head,
+        });
+    }
+
+    /// A row as it is displayed, with tabs expanded
+    pub fn display_line(&self, row: usize) -> String {
+        self.indentation.expand_tabs(&self.line(row))
+    }
+
+    /// The byte offset of `position` into its row as returned by `display_line`
+    pub fn display_column(&self, position: Position) -> usize {
+        // Tab stops only depend on what comes before them, so expanding the
+        // start of the row gives the start of the expanded row
+        let line = self.line(position.row);
+        let byte_column = self.byte_column(&line, position.column);
+        self.indentation.expand_tabs(&line[..byte_column]).len()
+    }
+
+    /// The language the content is highlighted as, if any
+    pub fn language(&self) -> Option<Language> {
+        self.highlighter.language()
+    }
+
+    /// The highlighted spans of each row in `rows`, as byte ranges into the
+    /// rows returned by `display_line`
+    pub fn highlights(&mut self, rows: Range<usize>) -> Vec<Vec<Span>> {
+        let highlights = match &mut self.syntax {
+            Some(syntax) => syntax.spans(&self.content, rows.clone()),
+            None => None,
+        }
+        .unwrap_or_else(|| self.highlighter.spans(&self.content, rows.clone()));
+        rows.zip(highlights)
+            .map(|(row, spans)| {
+                let line = self.line(row);
+                if !line.contains('\t') {
+                    return spans;
+                }
+                let display = |i| self.indentation.expand_tabs(&line[..i]).len();
+                spans
+                    .into_iter()
+                    .map(|span| Span {
+                        range: display(span.range.start)..display(span.range.end),
+                        ..span
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Search for `query`, selecting the first match from the start of the
+    /// selection. An empty query clears the search.
+    pub fn set_search(&mut self, query: &SearchQuery) -> Result<(), regex::Error> {
+        if query.text.is_empty() {
+            self.search = None;
+            return Ok(());
+        }
+        self.search = Some(Search::new(query)?);
+        let range = self.range_of(self.cursors.primary());
+        self.select_match(|matches| {
+            let first = matches.partition_point(|found| found.start < range.start);
+            first % matches.len()
+        });
+        Ok(())
+    }
+
+    pub fn clear_search(&mut self) {
+        self.search = None;
+    }
+
+    /// Which match is selected, if any, and how many there are
+    pub fn search_position(&mut self) -> Option<(Option<usize>, usize)> {
+        let range = self.range_of(self.cursors.primary());
+        let matches = self.search.as_mut()?.matches(&self.content);
+        let selected = matches.iter().position(|found| *found == range);
+        Some((selected, matches.len()))
+    }
+
+    /// The matches on each row in `rows`, as byte ranges into the rows
+    /// returned by `display_line`
+    pub fn search_matches(&mut self, rows: Range<usize>) -> Vec<Vec<Range<usize>>> {
+        let mut found_in_rows = vec![Vec::new(); rows.len()];
+        let Some(search) = &mut self.search else {
+            return found_in_rows;
+        };
+        let start = self.content.line_to_char(rows.start);
+        let end = self.content.line_to_char(rows.end);
+        let matches = search.matches(&self.content);
+        let first = matches.partition_point(|found| found.end <= start);
+        let visible: Vec<Range<usize>> = matches[first..]
+            .iter()
+            .take_while(|found| found.start < end)
+            .cloned()
+            .collect();
+        for found in visible {
+            let (from, to) = (self.position_of(found.start), self.position_of(found.end));
+            // A match can run over several rows
+            for row in from.row.max(rows.start)..=to.row.min(rows.end - 1) {
+                let row_start = if row == from.row {
+                    self.display_column(from)
+                } else {
+                    0
+                };
+                let row_end = if row == to.row {
+                    self.display_column(to)
+                } else {
+                    self.display_line(row).len()
+                };
+                found_in_rows[row - rows.start].push(row_start..row_end);
+            }
+        }
+        found_in_rows
+    }
+
+    /// Replace the selected match with `replacement` and select the next one.
+    /// If no match is selected, just select the next one.
+    pub fn replace_match(&mut self, replacement: &str) {
+        let range = self.range_of(self.cursors.primary());
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        if search.matches(&self.content).contains(&range) {
+            let text = search
+                .replacements(&self.content, replacement)
+                .into_iter()
+                .find(|(found, _)| *found == range)
+                .map(|(_, text)| text);
+            if let Some(text) = text {
+                self.replace_ranges(vec![(range, text)], false);
+            }
+        }
+        self.action(Action::FindNext);
+    }
+
+    /// Replace every match with `replacement` as a single undo step,
+    /// returning how many were replaced
+    pub fn replace_all(&mut self, replacement: &str) -> usize {
+        let Some(search) = &self.search else {
+            return 0;
+        };
+        let replacements = search.replacements(&self.content, replacement);
+        let count = replacements.len();
+        self.replace_ranges(replacements, false);
+        count
+    }
+
+    /// Select one of the search matches, chosen by `choose` from a non-empty list
+    fn select_match(&mut self, choose: impl FnOnce(&[Range<usize>]) -> usize) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let matches = search.matches(&self.content);
+        if matches.is_empty() {
+            return;
+        }
+        let found = matches[choose(matches)].clone();
+        self.cursors = Cursors::new(Selection {
+            anchor: self.position_of(found.start),
+            head: self.position_of(found.end),
+        });
+    }
+
+    /// The chars of the next occurrence of `selection`'s text after it that
+    /// isn't already selected, wrapping around to the start of the document
+    fn next_occurrence(&self, selection: Selection) -> Option<Range<usize>> {
+        let range = self.range_of(selection);
+        let needle = self.content.slice(range.clone()).to_string();
+        let text = self.content.to_string();
+        let after = self.content.char_to_byte(range.end);
+        let selected: Vec<Range<usize>> = self
+            .cursors
+            .all()
+            .iter()
+            .map(|selection| self.range_of(*selection))
+            .collect();
+        text[after..]
+            .match_indices(&needle)
+            .map(|(i, _)| after + i)
+            .chain(text[..after].match_indices(&needle).map(|(i, _)| i))
+            .map(|byte| {
+                let start = self.content.byte_to_char(byte);
+                start..start + range.len()
+            })
+            .find(|found| !selected.contains(found))
+    }
+
+    /// The word at `position`, or an empty selection there if it's in an
+    /// empty row
+    fn word_at(&self, position: Position) -> Selection {
+        let row = position.row.min(self.line_count().saturating_sub(1));
+        let line = self.line(row);
+        // Past the end of the line, pick the last word
+        let column = position.column.min(self.line_length(row).saturating_sub(1));
+        let mut start = 0;
+        for word in line.split_word_bounds() {
+            let end = start + word.graphemes(true).count();
+            if column < end {
+                return Selection {
+                    anchor: Position { row, column: start },
+                    head: Position { row, column: end },
+                };
+            }
+            start = end;
+        }
+        Selection::cursor(Position { row, column: start })
+    }
+
+    /// `position`, moved inside the document if it's past the end of its row
+    /// or of the document
+    fn clamp(&self, position: Position) -> Position {
+        let row = position.row.min(self.line_count().saturating_sub(1));
+        Position {
+            row,
+            column: position.column.min(self.line_length(row)),
+        }
+    }
+
+    /// The byte offset into `line` of a grapheme `column`
+    fn byte_column(&self, line: &str, column: usize) -> usize {
+        line.grapheme_indices(true)
+            .nth(column)
+            .map_or(line.len(), |(i, _)| i)
+    }
+
+    /// The text of a row, always ending in a line break
+    fn line_with_break(&self, row: usize) -> String {
+        let mut line = self.line(row).into_owned();
+        line.push('\n');
+        line
+    }
+
+    /// The length of a row in grapheme clusters, not counting its line break
+    pub fn line_length(&self, row: usize) -> usize {
+        self.line(row).graphemes(true).count()
+    }
+
+    /// The length of a row in chars, not counting its line break
+    fn line_char_length(&self, row: usize) -> usize {
+        let line = self.content.line(row);
+        let length = line.len_chars();
+        if length > 0 && line.char(length - 1) == '\n' {
+            length - 1
+        } else {
+            length
+        }
+    }
+
+    fn char_index(&self, position: Position) -> usize {
+        let column_chars: usize = self
+            .line(position.row)
+            .graphemes(true)
+            .take(position.column)
+            .map(|grapheme| grapheme.chars().count())
+            .sum();
+        self.content.line_to_char(position.row) + column_chars
+    }
+
+    /// The position of a char index. An index inside a grapheme cluster is
+    /// moved to the end of the cluster.
+    fn position_of(&self, index: usize) -> Position {
+        let row = self.content.char_to_line(index);
+        let column_chars = index - self.content.line_to_char(row);
+        let mut chars = 0;
+        let column = self
+            .line(row)
+            .graphemes(true)
+            .take_while(|grapheme| {
+                let before = chars;
+                chars += grapheme.chars().count();
+                before < column_chars
+            })
+            .count();
+        Position { row, column }
+    }
+
+    /// The chars covered by `selection`
+    fn range_of(&self, selection: Selection) -> Range<usize> {
+        self.char_index(selection.start())..self.char_index(selection.end())
+    }
+
+    /// Where `motion` takes the cursor from `head`
+    fn motion_target(&self, head: Position, motion: Motion) -> Position {
+        let last_row = self.line_count().saturating_sub(1);
+        match motion {
+            Motion::Up => {
+                let row = head.row.saturating_sub(1);
+                Position {
+                    row,
+                    column: head.column.min(self.line_length(row)),
+                }
+            }
+            Motion::Down => {
+                let row = head.row.saturating_add(1).min(last_row);
+                Position {
+                    row,
+                    column: head.column.min(self.line_length(row)),
+                }
+            }
+            Motion::Left => {
+                if head.column > 0 {
+                    Position {
+                        column: head.column - 1,
+                        ..head
+                    }
+                } else if head.row > 0 {
+                    Position {
+                        row: head.row - 1,
+                        column: self.line_length(head.row - 1),
+                    }
+                } else {
+                    head
+                }
+            }
+            Motion::Right => {
+                if head.column < self.line_length(head.row) {
+                    Position {
+                        column: head.column + 1,
+                        ..head
+                    }
+                } else if head.row < last_row {
+                    Position {
+                        row: head.row + 1,
+                        column: 0,
+                    }
+                } else {
+                    head
+                }
+            }
+            Motion::Home => Position { column: 0, ..head },
+            Motion::End => Position {
+                column: self.line_length(head.row),
+                ..head
+            },
+            Motion::PageUp => {
+                let row = head.row.saturating_sub(self.page_rows);
+                Position {
+                    row,
+                    column: head.column.min(self.line_length(row)),
+                }
+            }
+            Motion::PageDown => {
+                let row = head.row.saturating_add(self.page_rows).min(last_row);
+                Position {
+                    row,
+                    column: head.column.min(self.line_length(row)),
+                }
+            }
+            Motion::DocumentStart => Position::default(),
+            Motion::DocumentEnd => Position {
+                row: last_row,
+                column: self.line_length(last_row),
+            },
+            Motion::WordLeft | Motion::SubwordLeft => {
+                let stops = words::stops(&self.line(head.row), motion == Motion::SubwordLeft);
+                match stops.iter().rev().find(|(start, _)| *start < head.column) {
+                    Some((start, _)) => Position {
+                        column: *start,
+                        ..head
+                    },
+                    // Past the first word, go to the start of the row, then
+                    // on to the end of the previous one
+                    None if head.column > 0 => Position { column: 0, ..head },
+                    None => self.motion_target(head, Motion::Left),
+                }
+            }
+            Motion::WordRight | Motion::SubwordRight => {
+                let stops = words::stops(&self.line(head.row), motion == Motion::SubwordRight);
+                match stops.iter().find(|(_, end)| *end > head.column) {
+                    Some((_, end)) => Position {
+                        column: *end,
+                        ..head
+                    },
+                    None if head.column < self.line_length(head.row) => Position {
+                        column: self.line_length(head.row),
+                        ..head
+                    },
+                    None => self.motion_target(head, Motion::Right),
+                }
+            }
+        }
+    }
+
+    /// Delete each selection, or if it's empty, the text between the cursor
+    /// and where `motion` takes it
+    fn delete(&mut self, motion: Motion) {
+        self.replace_selections(false, |backend, selection, range| {
+            if !range.is_empty() {
+                return Some((range, String::new()));
+            }
+            let target = backend.char_index(backend.motion_target(selection.head, motion));
+            let range = target.min(range.start)..target.max(range.start);
+            (!range.is_empty()).then_some((range, String::new()))
+        });
+    }
+
+    /// Move every cursor, either dragging the selection's head along with it
+    /// (`extend`) or leaving an empty selection at the new position
+    fn move_cursor(&mut self, motion: Motion, extend: bool) {
+        self.map_selections(|backend, selection| {
+            if extend {
+                Selection {
+                    anchor: selection.anchor,
+                    head: backend.motion_target(selection.head, motion),
+                }
+            } else if !selection.is_empty() && matches!(motion, Motion::Left | Motion::Right) {
+                // Moving sideways out of a selection stops at its edge
+                let edge = if matches!(motion, Motion::Left) {
+                    selection.start()
+                } else {
+                    selection.end()
+                };
+                Selection::cursor(edge)
+            } else {
+                Selection::cursor(backend.motion_target(selection.head, motion))
+            }
+        });
+    }
+
+    /// Change every cursor's selection with `f`, merging any that end up
+    /// overlapping
+    fn map_selections(&mut self, mut f: impl FnMut(&Self, Selection) -> Selection) {
+        let selections = self
+            .cursors
+            .all()
+            .iter()
+            .map(|selection| f(self, *selection))
+            .collect();
+        self.cursors.update(selections);
+    }
+
+    /// Keep the highlighter and syntax tree in step with an edit at char
+    /// `position` that replaced `removed` with `inserted`
+    fn edited(&mut self, position: usize, removed: &str, inserted: &str) {
+        let row = self.content.char_to_line(position);
+        self.highlighter.edit(row, removed, inserted);
+        if let Some(syntax) = &mut self.syntax {
+            syntax.edit(&self.content, position, removed, inserted);
+        }
+        if let Some(search) = &mut self.search {
+            let (removed, inserted) = (removed.chars().count(), inserted.chars().count());
+            search.edit(&self.content, position, removed, inserted);
+        }
+        if !self.views.is_empty() {
+            // The text before the edit is untouched, so its start is where it was
+            let start = self.position_of(position);
+            for view in self.views.values_mut() {
+                view.cursors.shift(start, removed, inserted);
+                // Typing there next starts an undo step of its own, and
+                // the selections it expanded from are out of date
+                view.typing = false;
+                view.expansions.clear();
+            }
+        }
+    }
+
+    fn set_language(&mut self, language: Option<Language>) {
+        self.highlighter = Highlighter::new(language);
+        self.syntax = language.and_then(Syntax::new);
+    }
+
+    /// Replace the text at every cursor as a single undo step. `replacement`
+    /// is given each cursor's selection and the chars it covers, and returns
+    /// which chars to replace, if any, and what to put in their place.
+    fn replace_selections(
+        &mut self,
+        merge: bool,
+        mut replacement: impl FnMut(&Self, Selection, Range<usize>) -> Option<(Range<usize>, String)>,
+    ) {
+        let replacements = self
+            .cursors
+            .all()
+            .iter()
+            .filter_map(|selection| replacement(self, *selection, self.range_of(*selection)))
+            .collect();
+        self.replace_ranges(replacements, merge);
+    }
+
+    /// Replace several char ranges as a single undo step, joining any that
+    /// overlap. The cursors move along with the text around them, and any
+    /// end of a selection inside or at the start of a replaced range is left
+    /// after the new text. The change is merged into the previous undo step
+    /// if `merge` is set and both only insert text where the cursors were.
+    fn replace_ranges(&mut self, mut replacements: Vec<(Range<usize>, String)>, merge: bool) {
+        if replacements.is_empty() {
+            return;
+        }
+        replacements.sort_by_key(|(range, _)| range.start);
+        let mut joined: Vec<(Range<usize>, String)> = Vec::with_capacity(replacements.len());
+        for (range, text) in replacements {
+            match joined.last_mut() {
+                Some((last, last_text)) if range.start < last.end => {
+                    last.end = last.end.max(range.end);
+                    last_text.push_str(&text);
+                }
+                _ => joined.push((range, text)),
+            }
+        }
+
+        // Where a char index ends up once the replacements are made
+        let moves: Vec<(Range<usize>, usize)> = joined
+            .iter()
+            .map(|(range, text)| (range.clone(), text.chars().count()))
+            .collect();
+        let moved = |index: usize| {
+            let mut shift = 0;
+            for (range, inserted) in &moves {
+                if index < range.start {
+                    break;
+                }
+                if index < range.end {
+                    return range.start.saturating_add_signed(shift) + inserted;
+                }
+                shift += *inserted as isize - range.len() as isize;
+            }
+            index.saturating_add_signed(shift)
+        };
+        let ends: Vec<(usize, usize)> = self
+            .cursors
+            .all()
+            .iter()
+            .map(|selection| {
+                (
+                    self.char_index(selection.anchor),
+                    self.char_index(selection.head),
+                )
+            })
+            .collect();
+
+        let cursors_before = self.cursors.clone();
+        // Going backwards through the document keeps the earlier ranges valid
+        let edits: Vec<Edit> = joined
+            .into_iter()
+            .rev()
+            .map(|(range, text)| {
+                let edit = Edit {
+                    position: range.start,
+                    deleted: self.content.slice(range).to_string(),
+                    inserted: text,
+                };
+                edit.apply(&mut self.content);
+                self.edited(edit.position, &edit.deleted, &edit.inserted);
+                edit
+            })
+            .collect();
+        let selections = ends
+            .into_iter()
+            .map(|(anchor, head)| Selection {
+                anchor: self.position_of(moved(anchor)),
+                head: self.position_of(moved(head)),
+            })
+            .collect();
+        self.cursors.update(selections);
+
+        let only_inserts = |edits: &[Edit]| edits.iter().all(|edit| edit.deleted.is_empty());
+        let follows_last_step = self
+            .history
+            .last()
+            .is_some_and(|last| last.cursors_after == cursors_before && only_inserts(&last.edits));
+        if merge && follows_last_step && only_inserts(&edits) {
+            self.history.extend(edits, self.cursors.clone());
+        } else {
+            self.history
+                .record(edits, cursors_before, self.cursors.clone());
+        }
+    }
+
+    /// Add or remove one level of indentation on every row the selections touch
+    fn indent_lines(&mut self, dedent: bool) {
+        let mut rows: Vec<usize> = Vec::new();
+        for selection in self.cursors.all() {
+            let (start, end) = (selection.start(), selection.end());
+            // A selection ending at the start of a row doesn't include that row
+            let last_row = if end.row > start.row && end.column == 0 {
+                end.row - 1
+            } else {
+                end.row
+            };
+            rows.extend(start.row..=last_row);
+        }
+        // The selections are in order, so only neighbours can share a row
+        rows.dedup();
+        let replacements = rows
+            .into_iter()
+            .filter_map(|row| {
+                let line_start = self.content.line_to_char(row);
+                let line = self.line(row);
+                if dedent {
+                    let indent = self.indentation.leading_indent(&line);
+                    (indent > 0).then(|| (line_start..line_start + indent, String::new()))
+                } else {
+                    let indent = self.indentation.indent_from(0);
+                    (!line.is_empty()).then_some((line_start..line_start, indent))
+                }
+            })
+            .collect();
+        self.replace_ranges(replacements, false);
     }
 }
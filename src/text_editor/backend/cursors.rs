@@ -0,0 +1,147 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{Position, Selection};
+
+/// Every cursor's selection, in document order, along with the primary
+/// one, which is the one added last. Selections never overlap: any that
+/// come to overlap are merged into one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Cursors {
+    selections: Vec<Selection>,
+    primary: usize,
+}
+
+impl Default for Cursors {
+    fn default() -> Self {
+        Self::new(Selection::default())
+    }
+}
+
+impl Cursors {
+    /// A single cursor with `selection`
+    pub fn new(selection: Selection) -> Self {
+        Self {
+            selections: vec![selection],
+            primary: 0,
+        }
+    }
+
+    pub fn primary(&self) -> Selection {
+        self.selections[self.primary]
+    }
+
+    pub fn all(&self) -> &[Selection] {
+        &self.selections
+    }
+
+    pub fn len(&self) -> usize {
+        self.selections.len()
+    }
+
+    /// Add a cursor with `selection`, which becomes the primary one
+    pub fn add(&mut self, selection: Selection) {
+        self.selections.push(selection);
+        self.primary = self.selections.len() - 1;
+        self.merge();
+    }
+
+    /// Replace the primary selection, keeping the other cursors
+    pub fn set_primary(&mut self, selection: Selection) {
+        self.selections[self.primary] = selection;
+        self.merge();
+    }
+
+    /// Replace every selection with the one at the same place in `selections`
+    pub fn update(&mut self, selections: Vec<Selection>) {
+        debug_assert_eq!(selections.len(), self.selections.len());
+        self.selections = selections;
+        self.merge();
+    }
+
+    /// Move the selections along with the text around them, after the
+    /// `removed` text at `start` was replaced with `inserted`. Any end of a
+    /// selection inside the removed text goes to the start of the change.
+    pub fn shift(&mut self, start: Position, removed: &str, inserted: &str) {
+        let (removed_end, inserted_end) = (end_of(start, removed), end_of(start, inserted));
+        let moved = |position: Position| {
+            if position <= start {
+                position
+            } else if position < removed_end {
+                start
+            } else if position.row == removed_end.row {
+                Position {
+                    row: inserted_end.row,
+                    column: inserted_end.column + position.column - removed_end.column,
+                }
+            } else {
+                Position {
+                    row: position.row - removed_end.row + inserted_end.row,
+                    ..position
+                }
+            }
+        };
+        for selection in &mut self.selections {
+            selection.anchor = moved(selection.anchor);
+            selection.head = moved(selection.head);
+        }
+        self.merge();
+    }
+
+    /// Sort the selections and merge any that overlap. A cursor touching
+    /// the edge of a selection counts as overlapping it, but two selections
+    /// that only touch are kept apart.
+    fn merge(&mut self) {
+        let primary = self.selections[self.primary];
+        self.selections.sort_by_key(|selection| selection.start());
+        let mut merged: Vec<Selection> = Vec::with_capacity(self.selections.len());
+        self.primary = 0;
+        for selection in self.selections.drain(..) {
+            let overlaps = merged.last().is_some_and(|last| {
+                selection.start() < last.end()
+                    || (selection.start() == last.end()
+                        && (selection.is_empty() || last.is_empty()))
+            });
+            match merged.last_mut() {
+                Some(last) if overlaps => {
+                    let (start, end) = (last.start(), last.end().max(selection.end()));
+                    // Keep the direction of whichever has a head to keep
+                    let forwards = if last.is_empty() {
+                        selection.head >= selection.anchor
+                    } else {
+                        last.head >= last.anchor
+                    };
+                    *last = if forwards {
+                        Selection {
+                            anchor: start,
+                            head: end,
+                        }
+                    } else {
+                        Selection {
+                            anchor: end,
+                            head: start,
+                        }
+                    };
+                }
+                _ => merged.push(selection),
+            }
+            if selection == primary {
+                self.primary = merged.len() - 1;
+            }
+        }
+        self.selections = merged;
+    }
+}
+
+/// Where `text` ends if it's put at `start`
+fn end_of(start: Position, text: &str) -> Position {
+    match text.rsplit_once('\n') {
+        Some((before, last)) => Position {
+            row: start.row + before.matches('\n').count() + 1,
+            column: last.graphemes(true).count(),
+        },
+        None => Position {
+            column: start.column + text.graphemes(true).count(),
+            ..start
+        },
+    }
+}
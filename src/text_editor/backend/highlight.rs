@@ -0,0 +1,244 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::Path;
+
+use ropey::Rope;
+
+mod json;
+mod markdown;
+mod rust;
+mod toml;
+
+/// The languages that can be highlighted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Toml,
+    Markdown,
+    Json,
+}
+
+impl Language {
+    /// The language of a file, judged by its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(Self::Rust),
+            "toml" => Some(Self::Toml),
+            "md" | "markdown" => Some(Self::Markdown),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    fn tokenize(self, line: &str, state: LineState, spans: &mut Vec<Span>) -> LineState {
+        match self {
+            Self::Rust => rust::tokenize(line, state, spans),
+            Self::Toml => toml::tokenize(line, state, spans),
+            Self::Markdown => markdown::tokenize(line, state, spans),
+            Self::Json => json::tokenize(line, state, spans),
+        }
+    }
+}
+
+/// What a span of text is, which decides the color it's drawn in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    Keyword,
+    Type,
+    Function,
+    String,
+    Number,
+    /// Literals with a name, like `true` or `null`
+    Constant,
+    Comment,
+    Attribute,
+    /// Keys in TOML and JSON
+    Property,
+    Heading,
+    Emphasis,
+    Code,
+    Link,
+}
+
+/// A highlighted byte range of a row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub range: Range<usize>,
+    pub highlight: Highlight,
+}
+
+/// What a row ends inside of, which the next row starts in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum LineState {
+    #[default]
+    Normal,
+    /// How many comments deep
+    BlockComment(usize),
+    String,
+    /// How many `#`s close the string
+    RawString(usize),
+    /// A triple quoted string with the given quote
+    MultilineString(u8),
+    /// A fenced code block with the given fence character
+    CodeBlock(u8),
+    /// How many arrays deep a value is
+    Array(usize),
+}
+
+#[derive(Debug)]
+struct HighlightedLine {
+    start: LineState,
+    end: LineState,
+    spans: Vec<Span>,
+}
+
+/// Caches the spans of each row, so only rows that change, or that start
+/// in a different state because of a change above them, are tokenized again
+#[derive(Debug, Default)]
+pub(super) struct Highlighter {
+    language: Option<Language>,
+    lines: Vec<Option<HighlightedLine>>,
+    // Rows before this one are known to be up to date
+    checked: usize,
+}
+
+impl Highlighter {
+    pub fn new(language: Option<Language>) -> Self {
+        Self {
+            language,
+            ..Default::default()
+        }
+    }
+
+    pub fn language(&self) -> Option<Language> {
+        self.language
+    }
+
+    /// Forget the rows from `row` that held `removed` text, which now hold `inserted`
+    pub fn edit(&mut self, row: usize, removed: &str, inserted: &str) {
+        if row >= self.lines.len() {
+            return;
+        }
+        let end = (row + removed.matches('\n').count() + 1).min(self.lines.len());
+        let rows = inserted.matches('\n').count() + 1;
+        self.lines
+            .splice(row..end, std::iter::repeat_with(|| None).take(rows));
+        self.checked = self.checked.min(row);
+    }
+
+    /// The spans of each row in `rows`
+    pub fn spans(&mut self, content: &Rope, rows: Range<usize>) -> Vec<Vec<Span>> {
+        let Some(language) = self.language else {
+            return rows.map(|_| Vec::new()).collect();
+        };
+        self.lines.resize_with(content.len_lines(), || None);
+
+        let end = rows.end.min(self.lines.len());
+        let mut state = match self.checked {
+            0 => LineState::Normal,
+            row => self.lines[row - 1]
+                .as_ref()
+                .map_or_else(Default::default, |line| line.end),
+        };
+        for row in self.checked..end {
+            match &self.lines[row] {
+                Some(line) if line.start == state => state = line.end,
+                _ => {
+                    let text: Cow<str> = content.line(row).into();
+                    let mut spans = Vec::new();
+                    let end = language.tokenize(text.trim_end_matches('\n'), state, &mut spans);
+                    self.lines[row] = Some(HighlightedLine {
+                        start: state,
+                        end,
+                        spans,
+                    });
+                    state = end;
+                }
+            }
+        }
+        self.checked = self.checked.max(end);
+
+        rows.map(|row| match self.lines.get(row) {
+            Some(Some(line)) => line.spans.clone(),
+            _ => Vec::new(),
+        })
+        .collect()
+    }
+}
+
+fn push(spans: &mut Vec<Span>, range: Range<usize>, highlight: Highlight) {
+    if !range.is_empty() {
+        spans.push(Span { range, highlight });
+    }
+}
+
+/// Whether `byte` can be part of a word. Any non-ASCII byte counts, so
+/// words never end in the middle of a character.
+fn is_word(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte >= 0x80
+}
+
+/// The end of the word starting at `i`
+fn word_end(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && is_word(bytes[i]) {
+        i += 1;
+    }
+    i
+}
+
+/// The index past the closing `quote` of a string whose contents start at
+/// `i`, or `None` if the line ends first
+fn string_end(bytes: &[u8], mut i: usize, quote: u8) -> Option<usize> {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            byte if byte == quote => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// The end of a `/* */` comment, which may nest, starting `depth` comments
+/// deep at `i`. If the line ends first, returns how deep it still is.
+fn block_comment_end(bytes: &[u8], mut i: usize, mut depth: usize) -> Result<usize, usize> {
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"/*") {
+            depth += 1;
+            i += 2;
+        } else if bytes[i..].starts_with(b"*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return Ok(i);
+            }
+        } else {
+            i += 1;
+        }
+    }
+    Err(depth)
+}
+
+/// The end of the number starting at `i`, including any suffix, fraction or exponent
+fn number_end(bytes: &[u8], mut i: usize) -> usize {
+    let hex = bytes[i..].starts_with(b"0x") || bytes[i..].starts_with(b"0X");
+    while i < bytes.len() {
+        let byte = bytes[i];
+        let fraction = byte == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+        let exponent = matches!(byte, b'+' | b'-') && !hex && matches!(bytes[i - 1], b'e' | b'E');
+        if is_word(byte) || fraction || exponent {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+/// The next byte after `i` that isn't a space or tab
+fn next_non_blank(bytes: &[u8], i: usize) -> Option<u8> {
+    bytes[i..]
+        .iter()
+        .copied()
+        .find(|byte| !matches!(byte, b' ' | b'\t'))
+}
@@ -0,0 +1,65 @@
+use super::{
+    block_comment_end, is_word, next_non_blank, number_end, push, string_end, word_end, Highlight,
+    LineState, Span,
+};
+
+/// Tokenizes JSON, along with the comments that many JSON config files allow
+pub(super) fn tokenize(line: &str, state: LineState, spans: &mut Vec<Span>) -> LineState {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    if let LineState::BlockComment(depth) = state {
+        match block_comment_end(bytes, 0, depth) {
+            Ok(end) => {
+                push(spans, 0..end, Highlight::Comment);
+                i = end;
+            }
+            Err(depth) => {
+                push(spans, 0..bytes.len(), Highlight::Comment);
+                return LineState::BlockComment(depth);
+            }
+        }
+    }
+
+    while i < bytes.len() {
+        let start = i;
+        let rest = &bytes[i..];
+        match rest[0] {
+            b'/' if rest.starts_with(b"//") => {
+                push(spans, start..bytes.len(), Highlight::Comment);
+                break;
+            }
+            b'/' if rest.starts_with(b"/*") => match block_comment_end(bytes, i + 2, 1) {
+                Ok(end) => {
+                    push(spans, start..end, Highlight::Comment);
+                    i = end;
+                }
+                Err(depth) => {
+                    push(spans, start..bytes.len(), Highlight::Comment);
+                    return LineState::BlockComment(depth);
+                }
+            },
+            b'"' => {
+                i = string_end(bytes, i + 1, b'"').unwrap_or(bytes.len());
+                // A string followed by a colon is an object's key
+                let highlight = if next_non_blank(bytes, i) == Some(b':') {
+                    Highlight::Property
+                } else {
+                    Highlight::String
+                };
+                push(spans, start..i, highlight);
+            }
+            b'-' | b'0'..=b'9' => {
+                i = number_end(bytes, i + 1);
+                push(spans, start..i, Highlight::Number);
+            }
+            byte if is_word(byte) => {
+                i = word_end(bytes, i);
+                if matches!(&line[start..i], "true" | "false" | "null") {
+                    push(spans, start..i, Highlight::Constant);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    LineState::Normal
+}
@@ -0,0 +1,107 @@
+use super::{is_word, push, Highlight, LineState, Span};
+
+/// The fence character if `line` opens or closes a fenced code block
+fn fence(line: &str) -> Option<u8> {
+    let trimmed = line.trim_start_matches(' ');
+    ["```", "~~~"]
+        .into_iter()
+        .find(|fence| trimmed.starts_with(fence))
+        .map(|fence| fence.as_bytes()[0])
+}
+
+pub(super) fn tokenize(line: &str, state: LineState, spans: &mut Vec<Span>) -> LineState {
+    let bytes = line.as_bytes();
+    if let LineState::CodeBlock(fence_char) = state {
+        push(spans, 0..bytes.len(), Highlight::Code);
+        return if fence(line) == Some(fence_char) {
+            LineState::Normal
+        } else {
+            state
+        };
+    }
+    if let Some(fence_char) = fence(line) {
+        push(spans, 0..bytes.len(), Highlight::Code);
+        return LineState::CodeBlock(fence_char);
+    }
+
+    let indent = bytes.iter().take_while(|byte| **byte == b' ').count();
+    let rest = &bytes[indent..];
+    let hashes = rest.iter().take_while(|byte| **byte == b'#').count();
+    if (1..=6).contains(&hashes) && matches!(rest.get(hashes), None | Some(b' ')) {
+        push(spans, 0..bytes.len(), Highlight::Heading);
+        return LineState::Normal;
+    }
+    if rest.first() == Some(&b'>') {
+        push(spans, 0..bytes.len(), Highlight::Comment);
+        return LineState::Normal;
+    }
+
+    // List markers
+    let digits = rest.iter().take_while(|byte| byte.is_ascii_digit()).count();
+    let marker = match rest {
+        [b'-' | b'*' | b'+', b' ', ..] => 1,
+        _ if digits > 0 && matches!(rest.get(digits), Some(b'.' | b')')) => digits + 1,
+        _ => 0,
+    };
+    push(spans, indent..indent + marker, Highlight::Keyword);
+
+    let mut i = indent + marker;
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'`' => {
+                let ticks = bytes[i..].iter().take_while(|byte| **byte == b'`').count();
+                let closing = "`".repeat(ticks);
+                match line[i + ticks..].find(&closing) {
+                    Some(end) => {
+                        i += ticks + end + ticks;
+                        push(spans, start..i, Highlight::Code);
+                    }
+                    None => i += ticks,
+                }
+            }
+            delimiter @ (b'*' | b'_') => {
+                let run = bytes[i..]
+                    .iter()
+                    .take(3)
+                    .take_while(|byte| **byte == delimiter)
+                    .count();
+                let closing = &line[i..i + run];
+                // Emphasis can't start with a space, and underscores inside
+                // words don't count
+                let opens = bytes.get(i + run).is_some_and(|byte| *byte != b' ')
+                    && (delimiter == b'*' || i == 0 || !is_word(bytes[i - 1]));
+                match line[i + run..].find(closing).filter(|_| opens) {
+                    Some(end) if end > 0 => {
+                        i += run + end + run;
+                        push(spans, start..i, Highlight::Emphasis);
+                    }
+                    _ => i += run,
+                }
+            }
+            b'[' | b'!' if bytes[i..].starts_with(b"[") || bytes[i..].starts_with(b"![") => {
+                // A link or image is `[text](destination)`
+                let end = line[i..].find("](").and_then(|middle| {
+                    line[i + middle..].find(')').map(|end| i + middle + end + 1)
+                });
+                match end {
+                    Some(end) => {
+                        push(spans, start..end, Highlight::Link);
+                        i = end;
+                    }
+                    None => i += 1,
+                }
+            }
+            b'<' => match line[i..].find('>') {
+                Some(end) if line[i + 1..i + end].contains("://") => {
+                    i += end + 1;
+                    push(spans, start..i, Highlight::Link);
+                }
+                _ => i += 1,
+            },
+            _ => i += 1,
+        }
+    }
+    LineState::Normal
+}
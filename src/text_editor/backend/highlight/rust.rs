@@ -0,0 +1,177 @@
+use super::{
+    block_comment_end, next_non_blank, number_end, push, string_end, word_end, Highlight,
+    LineState, Span,
+};
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "type", "unsafe", "use", "where",
+    "while", "yield",
+];
+
+const PRIMITIVES: &[&str] = &[
+    "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64",
+    "i128", "isize", "f32", "f64",
+];
+
+/// Where the string or comment that `state` is inside ends
+fn close(bytes: &[u8], i: usize, state: LineState) -> Result<usize, LineState> {
+    match state {
+        LineState::BlockComment(depth) => {
+            block_comment_end(bytes, i, depth).map_err(LineState::BlockComment)
+        }
+        LineState::RawString(hashes) => {
+            let mut closing = vec![b'"'];
+            closing.resize(hashes + 1, b'#');
+            bytes[i..]
+                .windows(closing.len())
+                .position(|window| window == closing.as_slice())
+                .map(|position| i + position + closing.len())
+                .ok_or(state)
+        }
+        _ => string_end(bytes, i, b'"').ok_or(LineState::String),
+    }
+}
+
+/// The number of `#`s and the length of the prefix if a raw string starts at `i`
+fn raw_string_start(bytes: &[u8], i: usize) -> Option<(usize, usize)> {
+    let prefix = if bytes[i..].starts_with(b"br") {
+        2
+    } else if bytes[i] == b'r' {
+        1
+    } else {
+        return None;
+    };
+    let hashes = bytes[i + prefix..]
+        .iter()
+        .take_while(|byte| **byte == b'#')
+        .count();
+    (bytes.get(i + prefix + hashes) == Some(&b'"')).then_some((hashes, prefix + hashes + 1))
+}
+
+pub(super) fn tokenize(line: &str, state: LineState, spans: &mut Vec<Span>) -> LineState {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    if state != LineState::Normal {
+        let highlight = match state {
+            LineState::BlockComment(_) => Highlight::Comment,
+            _ => Highlight::String,
+        };
+        match close(bytes, 0, state) {
+            Ok(end) => {
+                push(spans, 0..end, highlight);
+                i = end;
+            }
+            Err(state) => {
+                push(spans, 0..bytes.len(), highlight);
+                return state;
+            }
+        }
+    }
+
+    // Whether the last word was `fn`, so the next one names a function
+    let mut after_fn = false;
+    while i < bytes.len() {
+        let start = i;
+        let rest = &bytes[i..];
+        let opened = if rest.starts_with(b"//") {
+            push(spans, start..bytes.len(), Highlight::Comment);
+            return LineState::Normal;
+        } else if rest.starts_with(b"/*") {
+            Some((LineState::BlockComment(1), i + 2, Highlight::Comment))
+        } else if rest[0] == b'"' {
+            Some((LineState::String, i + 1, Highlight::String))
+        } else if rest.starts_with(b"b\"") {
+            Some((LineState::String, i + 2, Highlight::String))
+        } else if let Some((hashes, length)) = raw_string_start(bytes, i) {
+            Some((LineState::RawString(hashes), i + length, Highlight::String))
+        } else {
+            None
+        };
+        if let Some((state, contents, highlight)) = opened {
+            match close(bytes, contents, state) {
+                Ok(end) => {
+                    push(spans, start..end, highlight);
+                    i = end;
+                    continue;
+                }
+                Err(state) => {
+                    push(spans, start..bytes.len(), highlight);
+                    return state;
+                }
+            }
+        }
+
+        match rest[0] {
+            b'\'' | b'b' if rest.starts_with(b"b'") || rest[0] == b'\'' => {
+                let quote = if rest[0] == b'b' { i + 1 } else { i };
+                // A char literal is one (possibly escaped) char between quotes,
+                // anything else is a lifetime or label
+                let char_end = if bytes.get(quote + 1) == Some(&b'\\') {
+                    string_end(bytes, quote + 1, b'\'')
+                } else {
+                    line[quote + 1..]
+                        .chars()
+                        .next()
+                        .map(|c| quote + 1 + c.len_utf8())
+                        .filter(|end| bytes.get(*end) == Some(&b'\''))
+                        .map(|end| end + 1)
+                };
+                if let Some(end) = char_end {
+                    push(spans, start..end, Highlight::String);
+                    i = end;
+                } else if rest[0] == b'\'' {
+                    i = word_end(bytes, i + 1);
+                    push(spans, start..i, Highlight::Keyword);
+                } else {
+                    // Just a word starting with b
+                    i = word_end(bytes, i);
+                }
+            }
+            b'#' if rest.starts_with(b"#[") || rest.starts_with(b"#![") => {
+                let mut depth = 0;
+                i = bytes[i..]
+                    .iter()
+                    .position(|byte| {
+                        match byte {
+                            b'[' => depth += 1,
+                            b']' => depth -= 1,
+                            _ => return false,
+                        }
+                        depth == 0
+                    })
+                    .map_or(bytes.len(), |position| i + position + 1);
+                push(spans, start..i, Highlight::Attribute);
+            }
+            byte if byte.is_ascii_digit() => {
+                i = number_end(bytes, i);
+                push(spans, start..i, Highlight::Number);
+            }
+            byte if super::is_word(byte) => {
+                i = word_end(bytes, i);
+                let word = &line[start..i];
+                let next = next_non_blank(bytes, i);
+                let highlight = if next == Some(b'!') && bytes.get(i + 1) != Some(&b'=') {
+                    Some(Highlight::Function)
+                } else if KEYWORDS.contains(&word) {
+                    Some(Highlight::Keyword)
+                } else if word == "true" || word == "false" {
+                    Some(Highlight::Constant)
+                } else if PRIMITIVES.contains(&word) || word.starts_with(char::is_uppercase) {
+                    Some(Highlight::Type)
+                } else if after_fn || next == Some(b'(') {
+                    Some(Highlight::Function)
+                } else {
+                    None
+                };
+                if let Some(highlight) = highlight {
+                    push(spans, start..i, highlight);
+                }
+                after_fn = word == "fn";
+            }
+            _ => i += 1,
+        }
+    }
+    LineState::Normal
+}
@@ -0,0 +1,150 @@
+use super::{push, string_end, Highlight, LineState, Span};
+
+/// Where the string that `state` is inside ends
+fn close(bytes: &[u8], mut i: usize, state: LineState) -> Result<usize, LineState> {
+    let LineState::MultilineString(quote) = state else {
+        return Ok(i);
+    };
+    let closing = [quote; 3];
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && quote == b'"' {
+            i += 2;
+        } else if bytes[i..].starts_with(&closing) {
+            // Up to two more quotes can end the string's contents
+            let quotes = bytes[i..]
+                .iter()
+                .take(5)
+                .take_while(|b| **b == quote)
+                .count();
+            return Ok(i + quotes);
+        } else {
+            i += 1;
+        }
+    }
+    Err(state)
+}
+
+/// Whether `byte` can be part of a bare key or a bare value like a number or date
+fn is_bare(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-' | b'+' | b'.' | b':')
+}
+
+pub(super) fn tokenize(line: &str, state: LineState, spans: &mut Vec<Span>) -> LineState {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    if let LineState::MultilineString(_) = state {
+        match close(bytes, 0, state) {
+            Ok(end) => {
+                push(spans, 0..end, Highlight::String);
+                i = end;
+            }
+            Err(state) => {
+                push(spans, 0..bytes.len(), Highlight::String);
+                return state;
+            }
+        }
+    }
+
+    // The brackets and braces the value is inside. Only arrays can go on
+    // past the end of a line.
+    let mut nesting = match state {
+        LineState::Array(depth) => vec![b'['; depth],
+        _ => Vec::new(),
+    };
+    // Keys come before the `=`, at the start of a line or in an inline table
+    let mut is_key = i == 0 && nesting.is_empty();
+    while i < bytes.len() {
+        let start = i;
+        let rest = &bytes[i..];
+        match rest[0] {
+            b'#' => {
+                push(spans, start..bytes.len(), Highlight::Comment);
+                break;
+            }
+            b'[' if is_key && nesting.is_empty() => {
+                // A table header
+                i = line[i..].find(']').map_or(bytes.len(), |end| i + end + 1);
+                if bytes.get(i) == Some(&b']') {
+                    i += 1;
+                }
+                push(spans, start..i, Highlight::Type);
+                is_key = false;
+            }
+            quote @ (b'"' | b'\'') => {
+                if rest.starts_with(&[quote; 3]) {
+                    let state = LineState::MultilineString(quote);
+                    match close(bytes, i + 3, state) {
+                        Ok(end) => i = end,
+                        Err(state) => {
+                            push(spans, start..bytes.len(), Highlight::String);
+                            return state;
+                        }
+                    }
+                } else if quote == b'"' {
+                    i = string_end(bytes, i + 1, quote).unwrap_or(bytes.len());
+                } else {
+                    i = line[i + 1..]
+                        .find('\'')
+                        .map_or(bytes.len(), |end| i + 1 + end + 1);
+                }
+                let highlight = if is_key {
+                    Highlight::Property
+                } else {
+                    Highlight::String
+                };
+                push(spans, start..i, highlight);
+            }
+            byte if is_bare(byte) => {
+                while i < bytes.len() && is_bare(bytes[i]) {
+                    // Dotted keys are highlighted a part at a time
+                    if is_key && bytes[i] == b'.' {
+                        break;
+                    }
+                    i += 1;
+                }
+                let word = &line[start..i];
+                let highlight = if is_key {
+                    Some(Highlight::Property)
+                } else if word == "true" || word == "false" {
+                    Some(Highlight::Constant)
+                } else if word.starts_with(|c: char| c.is_ascii_digit() || "+-".contains(c))
+                    || word.ends_with("inf")
+                    || word.ends_with("nan")
+                {
+                    Some(Highlight::Number)
+                } else {
+                    None
+                };
+                if let Some(highlight) = highlight {
+                    push(spans, start..i, highlight);
+                }
+                if i == start {
+                    i += 1;
+                }
+            }
+            b'=' => {
+                is_key = false;
+                i += 1;
+            }
+            b'{' | b'[' => {
+                nesting.push(rest[0]);
+                is_key = rest[0] == b'{';
+                i += 1;
+            }
+            b'}' | b']' => {
+                nesting.pop();
+                i += 1;
+            }
+            b',' => {
+                is_key = nesting.last() == Some(&b'{');
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    if nesting.is_empty() {
+        LineState::Normal
+    } else {
+        LineState::Array(nesting.len())
+    }
+}
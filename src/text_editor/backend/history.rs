@@ -0,0 +1,146 @@
+use ropey::Rope;
+
+use super::Cursors;
+
+/// A single invertible change to the document: `deleted` was removed
+/// from `position` and `inserted` was put in its place.
+#[derive(Debug, Clone)]
+pub(super) struct Edit {
+    pub position: usize, // A char index into the document
+    pub deleted: String,
+    pub inserted: String,
+}
+
+impl Edit {
+    pub fn apply(&self, content: &mut Rope) {
+        let deleted_end = self.position + self.deleted.chars().count();
+        content.remove(self.position..deleted_end);
+        content.insert(self.position, &self.inserted);
+    }
+
+    pub fn revert(&self, content: &mut Rope) {
+        let inserted_end = self.position + self.inserted.chars().count();
+        content.remove(self.position..inserted_end);
+        content.insert(self.position, &self.deleted);
+    }
+}
+
+/// A group of edits that is undone and redone as one step
+#[derive(Debug)]
+pub(super) struct Transaction {
+    pub edits: Vec<Edit>,
+    pub cursors_before: Cursors,
+    pub cursors_after: Cursors,
+    // Names the document as it is after the step, so a saved state can be
+    // recognized again after undoing and redoing
+    state: usize,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct History {
+    undo_stack: Vec<Transaction>,
+    redo_stack: Vec<Transaction>,
+    states: usize,        // How many states have been named
+    saved: Option<usize>, // The state that was saved, `None` before any step
+}
+
+impl History {
+    /// Add a new undo step
+    pub fn record(&mut self, edits: Vec<Edit>, cursors_before: Cursors, cursors_after: Cursors) {
+        self.redo_stack.clear();
+        let state = self.new_state();
+        self.undo_stack.push(Transaction {
+            edits,
+            cursors_before,
+            cursors_after,
+            state,
+        });
+    }
+
+    /// Add `edits` to the most recent undo step
+    pub fn extend(&mut self, edits: Vec<Edit>, cursors_after: Cursors) {
+        self.redo_stack.clear();
+        let state = self.new_state();
+        match self.undo_stack.last_mut() {
+            Some(transaction) => {
+                transaction.edits.extend(edits);
+                transaction.cursors_after = cursors_after;
+                transaction.state = state;
+            }
+            None => unreachable!("Extended a history with no undo steps"),
+        }
+    }
+
+    /// The number of undo steps
+    pub fn len(&self) -> usize {
+        self.undo_stack.len()
+    }
+
+    /// Join the undo steps from the `start`th onwards into one
+    pub fn squash(&mut self, start: usize) {
+        if self.undo_stack.len() <= start + 1 {
+            return;
+        }
+        let mut steps = self.undo_stack.drain(start..);
+        let Some(mut first) = steps.next() else {
+            return;
+        };
+        for step in steps {
+            first.edits.extend(step.edits);
+            first.cursors_after = step.cursors_after;
+            first.state = step.state;
+        }
+        self.undo_stack.push(first);
+    }
+
+    /// The most recent undo step, if it can still be extended. The saved
+    /// step can't, so undoing can always get back to it.
+    pub fn last(&self) -> Option<&Transaction> {
+        if !self.redo_stack.is_empty() || self.is_saved() {
+            return None;
+        }
+        self.undo_stack.last()
+    }
+
+    fn new_state(&mut self) -> usize {
+        self.states += 1;
+        self.states
+    }
+
+    /// The state the document is in now
+    fn state(&self) -> Option<usize> {
+        self.undo_stack.last().map(|transaction| transaction.state)
+    }
+
+    /// Remember the current state as the one on disk
+    pub fn mark_saved(&mut self) {
+        self.saved = self.state();
+    }
+
+    /// Whether undoing and redoing led back to the state on disk
+    pub fn is_saved(&self) -> bool {
+        self.state() == self.saved
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Move the most recent undo step onto the redo stack and return it
+    pub fn undo(&mut self) -> Option<&Transaction> {
+        let transaction = self.undo_stack.pop()?;
+        self.redo_stack.push(transaction);
+        self.redo_stack.last()
+    }
+
+    /// Move the most recent redo step back onto the undo stack and return it
+    pub fn redo(&mut self) -> Option<&Transaction> {
+        let transaction = self.redo_stack.pop()?;
+        self.undo_stack.push(transaction);
+        self.undo_stack.last()
+    }
+}
@@ -0,0 +1,59 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How tabs are displayed and what the Tab key inserts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Indentation {
+    /// The distance between tab stops, in columns
+    pub tab_width: usize,
+    /// Whether indenting inserts spaces up to the next tab stop instead of a tab
+    pub insert_spaces: bool,
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            insert_spaces: true,
+        }
+    }
+}
+
+impl Indentation {
+    /// `text` with each tab replaced by spaces up to the next tab stop
+    pub fn expand_tabs(&self, text: &str) -> String {
+        let mut expanded = String::with_capacity(text.len());
+        let mut column = 0;
+        for grapheme in text.graphemes(true) {
+            if grapheme == "\t" {
+                let spaces = self.tab_width - column % self.tab_width;
+                expanded.push_str(&" ".repeat(spaces));
+                column += spaces;
+            } else {
+                expanded.push_str(grapheme);
+                column += 1;
+            }
+        }
+        expanded
+    }
+
+    /// What to insert to reach the next tab stop from `column`
+    pub fn indent_from(&self, column: usize) -> String {
+        if self.insert_spaces {
+            " ".repeat(self.tab_width - column % self.tab_width)
+        } else {
+            "\t".to_string()
+        }
+    }
+
+    /// How many chars of one level of indentation `line` starts with
+    pub fn leading_indent(&self, line: &str) -> usize {
+        if line.starts_with('\t') {
+            1
+        } else {
+            line.chars()
+                .take(self.tab_width)
+                .take_while(|c| *c == ' ')
+                .count()
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+use regex_syntax::hir::{Class, Hir, HirKind};
+use ropey::Rope;
+
+/// What to search the content for
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub text: String,
+    /// Whether `text` is a regular expression rather than plain text
+    pub regex: bool,
+    pub case_sensitive: bool,
+    /// Whether matches have to start and end on word boundaries
+    pub whole_word: bool,
+}
+
+impl SearchQuery {
+    fn pattern(&self) -> String {
+        let pattern = if self.regex {
+            self.text.clone()
+        } else {
+            regex::escape(&self.text)
+        };
+        if self.whole_word {
+            format!(r"\b(?:{pattern})\b")
+        } else {
+            pattern
+        }
+    }
+
+    fn to_regex(&self) -> Result<Regex, regex::Error> {
+        RegexBuilder::new(&self.pattern())
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+    }
+
+    /// Whether a match could run over a line break
+    fn spans_lines(&self) -> bool {
+        regex_syntax::ParserBuilder::new()
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+            .parse(&self.pattern())
+            .map_or(true, |hir| matches_line_break(&hir))
+    }
+}
+
+fn matches_line_break(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => false,
+        HirKind::Literal(literal) => literal.0.contains(&b'\n'),
+        HirKind::Class(Class::Unicode(class)) => class
+            .ranges()
+            .iter()
+            .any(|range| (range.start()..=range.end()).contains(&'\n')),
+        HirKind::Class(Class::Bytes(class)) => class
+            .ranges()
+            .iter()
+            .any(|range| (range.start()..=range.end()).contains(&b'\n')),
+        HirKind::Repetition(repetition) => matches_line_break(&repetition.sub),
+        HirKind::Capture(capture) => matches_line_break(&capture.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().any(matches_line_break),
+    }
+}
+
+/// The char ranges of the non-empty matches on each row in `rows`
+fn find_in_rows(regex: &Regex, content: &Rope, rows: Range<usize>) -> Vec<Range<usize>> {
+    let mut line_start = content.line_to_char(rows.start);
+    let mut found = Vec::new();
+    for line in content.lines_at(rows.start).take(rows.len()) {
+        // Only lines split between chunks are copied
+        let text: Cow<str> = line.into();
+        found.extend(
+            regex
+                .find_iter(&text)
+                .filter(|found| !found.is_empty())
+                .map(|found| {
+                    line_start + line.byte_to_char(found.start())
+                        ..line_start + line.byte_to_char(found.end())
+                }),
+        );
+        line_start += line.len_chars();
+    }
+    found
+}
+
+/// A query and its matches in the content. As long as no match can run
+/// over a line break, only the rows an edit touches are searched again.
+/// Otherwise the whole content is, the next time the matches are needed.
+#[derive(Debug)]
+pub(super) struct Search {
+    regex: Regex,
+    // Whether replacements can refer to capture groups, like `$1`
+    expand: bool,
+    spans_lines: bool,
+    matches: Option<Vec<Range<usize>>>, // Char ranges
+}
+
+impl Search {
+    pub fn new(query: &SearchQuery) -> Result<Self, regex::Error> {
+        Ok(Self {
+            regex: query.to_regex()?,
+            expand: query.regex,
+            spans_lines: query.spans_lines(),
+            matches: None,
+        })
+    }
+
+    /// Keep the matches in step with an edit at char `position` that
+    /// replaced `removed` chars with `inserted` ones
+    pub fn edit(&mut self, content: &Rope, position: usize, removed: usize, inserted: usize) {
+        if self.spans_lines {
+            self.matches = None;
+        }
+        let Some(matches) = &mut self.matches else {
+            return;
+        };
+        let first_row = content.char_to_line(position);
+        let last_row = content.char_to_line(position + inserted);
+        let start = content.line_to_char(first_row);
+        let end = content.line_to_char(last_row) + content.line(last_row).len_chars();
+        // Where the rows ended before the edit
+        let old_end = end + removed - inserted;
+        let before = matches.partition_point(|found| found.end <= start);
+        let after = matches.partition_point(|found| found.start < old_end);
+        let shifted: Vec<Range<usize>> = matches[after..]
+            .iter()
+            .map(|found| found.start + inserted - removed..found.end + inserted - removed)
+            .collect();
+        let found = find_in_rows(&self.regex, content, first_row..last_row + 1);
+        matches.splice(before.., found.into_iter().chain(shifted));
+    }
+
+    /// The char ranges of every match, in order. Empty matches are left out
+    /// as there would be nothing to select.
+    pub fn matches(&mut self, content: &Rope) -> &[Range<usize>] {
+        self.matches.get_or_insert_with(|| {
+            if !self.spans_lines {
+                return find_in_rows(&self.regex, content, 0..content.len_lines());
+            }
+            let text = content.to_string();
+            self.regex
+                .find_iter(&text)
+                .filter(|found| !found.is_empty())
+                .map(|found| content.byte_to_char(found.start())..content.byte_to_char(found.end()))
+                .collect()
+        })
+    }
+
+    /// The replacement for each match, with any capture groups filled in
+    pub fn replacements(&self, content: &Rope, replacement: &str) -> Vec<(Range<usize>, String)> {
+        let text = content.to_string();
+        self.regex
+            .captures_iter(&text)
+            .filter_map(|captures| {
+                let found = captures.get(0)?;
+                if found.is_empty() {
+                    return None;
+                }
+                let mut text = String::new();
+                if self.expand {
+                    captures.expand(replacement, &mut text);
+                } else {
+                    text.push_str(replacement);
+                }
+                let range = content.byte_to_char(found.start())..content.byte_to_char(found.end());
+                Some((range, text))
+            })
+            .collect()
+    }
+}
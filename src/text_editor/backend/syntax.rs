@@ -0,0 +1,234 @@
+use std::fmt;
+use std::ops::Range;
+
+use ropey::Rope;
+use tree_sitter::{InputEdit, Node, Parser, Point, Query, QueryCursor, Tree};
+
+use super::{Highlight, Language, Span};
+
+// The query bundled with the JSON grammar highlights keys as keywords and
+// braces as escapes, so keys are picked out as properties here instead
+const JSON_HIGHLIGHT_QUERY: &str = r#"
+(pair key: (string) @property)
+(string) @string
+(number) @number
+[(true) (false) (null)] @constant
+"#;
+
+/// A tree-sitter syntax tree of the content, which is edited along with
+/// it and reparsed incrementally the next time it's needed
+pub(super) struct Syntax {
+    parser: Parser,
+    tree: Option<Tree>,
+    query: Query,
+    // Whether the tree has been edited since it was parsed
+    stale: bool,
+}
+
+impl fmt::Debug for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Syntax")
+            .field("stale", &self.stale)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The bytes of `content` from `byte` to the end of its chunk
+fn read(content: &Rope, byte: usize) -> &[u8] {
+    if byte >= content.len_bytes() {
+        return &[];
+    }
+    let (chunk, chunk_start, _, _) = content.chunk_at_byte(byte);
+    &chunk.as_bytes()[byte - chunk_start..]
+}
+
+/// What a capture from a highlight query is drawn as
+fn highlight_of(capture: &str, node: Node) -> Option<Highlight> {
+    if matches!(node.kind(), "integer_literal" | "float_literal" | "number") {
+        return Some(Highlight::Number);
+    }
+    let highlight = match capture.split('.').next()? {
+        "keyword" | "label" => Highlight::Keyword,
+        "type" | "constructor" => Highlight::Type,
+        "function" => Highlight::Function,
+        "string" | "escape" => Highlight::String,
+        "number" => Highlight::Number,
+        "constant" => Highlight::Constant,
+        "comment" => Highlight::Comment,
+        "attribute" => Highlight::Attribute,
+        "property" => Highlight::Property,
+        "variable" if capture == "variable.builtin" => Highlight::Keyword,
+        _ => return None,
+    };
+    Some(highlight)
+}
+
+fn bracket_pair(kind: &str) -> Option<(&'static str, bool)> {
+    match kind {
+        "(" => Some((")", true)),
+        "[" => Some(("]", true)),
+        "{" => Some(("}", true)),
+        ")" => Some(("(", false)),
+        "]" => Some(("[", false)),
+        "}" => Some(("{", false)),
+        _ => None,
+    }
+}
+
+impl Syntax {
+    /// A syntax tree for `language`, if it has a bundled grammar
+    pub fn new(language: Language) -> Option<Self> {
+        let (grammar, query) = match language {
+            Language::Rust => (
+                tree_sitter_rust::language(),
+                tree_sitter_rust::HIGHLIGHT_QUERY,
+            ),
+            Language::Json => (tree_sitter_json::language(), JSON_HIGHLIGHT_QUERY),
+            Language::Toml | Language::Markdown => return None,
+        };
+        let mut parser = Parser::new();
+        parser.set_language(grammar).ok()?;
+        Some(Self {
+            parser,
+            tree: None,
+            query: Query::new(grammar, query).ok()?,
+            stale: true,
+        })
+    }
+
+    /// Update the tree for an edit at char `position` of `content`, which
+    /// has already replaced `removed` with `inserted`
+    pub fn edit(&mut self, content: &Rope, position: usize, removed: &str, inserted: &str) {
+        self.stale = true;
+        let Some(tree) = &mut self.tree else {
+            return;
+        };
+        let start_byte = content.char_to_byte(position);
+        let row = content.byte_to_line(start_byte);
+        let start = Point::new(row, start_byte - content.line_to_byte(row));
+        let end_of = |text: &str| match text.rfind('\n') {
+            Some(last_break) => Point::new(
+                row + text.matches('\n').count(),
+                text.len() - last_break - 1,
+            ),
+            None => Point::new(row, start.column + text.len()),
+        };
+        tree.edit(&InputEdit {
+            start_byte,
+            old_end_byte: start_byte + removed.len(),
+            new_end_byte: start_byte + inserted.len(),
+            start_position: start,
+            old_end_position: end_of(removed),
+            new_end_position: end_of(inserted),
+        });
+    }
+
+    /// Bring the tree up to date with `content`, reusing what's unchanged
+    fn reparse(&mut self, content: &Rope) -> Option<&Tree> {
+        if self.stale {
+            self.tree = self
+                .parser
+                .parse_with(&mut |byte, _| read(content, byte), self.tree.as_ref());
+            self.stale = false;
+        }
+        self.tree.as_ref()
+    }
+
+    /// The highlighted spans of each row in `rows`, as byte ranges into the row
+    pub fn spans(&mut self, content: &Rope, rows: Range<usize>) -> Option<Vec<Vec<Span>>> {
+        let tree = self.reparse(content)?.clone();
+        let start = content.line_to_byte(rows.start);
+        let end = content.line_to_byte(rows.end);
+
+        // Each byte is painted by the innermost capture around it. When
+        // several patterns capture the same node, the first one wins.
+        let mut captures: Vec<(Range<usize>, Highlight)> = Vec::new();
+        let mut painted: Vec<Option<usize>> = vec![None; end - start];
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(start..end);
+        let text = |node: Node| {
+            content
+                .byte_slice(node.byte_range())
+                .chunks()
+                .map(str::as_bytes)
+        };
+        for (query_match, index) in cursor.captures(&self.query, tree.root_node(), text) {
+            let capture = query_match.captures[index];
+            let name = &self.query.capture_names()[capture.index as usize];
+            let Some(highlight) = highlight_of(name, capture.node) else {
+                continue;
+            };
+            let range = capture.node.byte_range();
+            for byte in range.start.max(start)..range.end.min(end) {
+                let paint = &mut painted[byte - start];
+                let is_inside = paint.is_none_or(|other| {
+                    let other: &Range<usize> = &captures[other].0;
+                    other.start <= range.start && range.end <= other.end && *other != range
+                });
+                if is_inside {
+                    *paint = Some(captures.len());
+                }
+            }
+            captures.push((range, highlight));
+        }
+
+        let spans = rows
+            .map(|row| {
+                let line = content.line(row);
+                let has_break = line.len_chars() > 0 && line.char(line.len_chars() - 1) == '\n';
+                let row_start = content.line_to_byte(row);
+                let row_end = row_start + line.len_bytes() - usize::from(has_break);
+                let mut spans: Vec<Span> = Vec::new();
+                for byte in row_start..row_end {
+                    let Some(highlight) = painted[byte - start].map(|capture| captures[capture].1)
+                    else {
+                        continue;
+                    };
+                    let offset = byte - row_start;
+                    match spans.last_mut() {
+                        Some(span) if span.range.end == offset && span.highlight == highlight => {
+                            span.range.end += 1;
+                        }
+                        _ => spans.push(Span {
+                            range: offset..offset + 1,
+                            highlight,
+                        }),
+                    }
+                }
+                spans
+            })
+            .collect();
+        Some(spans)
+    }
+
+    /// The smallest syntax node bigger than the byte `range`
+    pub fn expand(&mut self, content: &Rope, range: Range<usize>) -> Option<Range<usize>> {
+        let tree = self.reparse(content)?;
+        let mut node = tree
+            .root_node()
+            .descendant_for_byte_range(range.start, range.end)?;
+        while node.byte_range() == range {
+            node = node.parent()?;
+        }
+        Some(node.byte_range())
+    }
+
+    /// The start of the bracket matching the one starting at `byte`
+    pub fn matching_bracket(&mut self, content: &Rope, byte: usize) -> Option<usize> {
+        let tree = self.reparse(content)?;
+        let bracket = tree.root_node().descendant_for_byte_range(byte, byte + 1)?;
+        if bracket.start_byte() != byte {
+            return None;
+        }
+        let (matching, is_opening) = bracket_pair(bracket.kind())?;
+        let parent = bracket.parent()?;
+        let mut cursor = parent.walk();
+        let mut siblings = parent.children(&mut cursor);
+        let found = if is_opening {
+            siblings.filter(|node| node.kind() == matching).last()
+        } else {
+            siblings.find(|node| node.kind() == matching)
+        };
+        found.map(|node| node.start_byte())
+    }
+}
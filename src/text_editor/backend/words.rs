@@ -0,0 +1,69 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The grapheme column ranges of the words in `line` that word motions stop
+/// at, using Unicode word boundaries. A run of punctuation counts as a word
+/// of its own, while whitespace is skipped. With `subwords`, words are split
+/// further at camelCase humps and underscores.
+pub(super) fn stops(line: &str, subwords: bool) -> Vec<(usize, usize)> {
+    let mut stops: Vec<(usize, usize)> = Vec::new();
+    let mut column = 0;
+    let mut after_punctuation = false;
+    for word in line.split_word_bounds() {
+        let graphemes: Vec<&str> = word.graphemes(true).collect();
+        let end = column + graphemes.len();
+        let is_punctuation = !word.chars().any(char::is_alphanumeric);
+        if word.chars().all(char::is_whitespace) {
+            after_punctuation = false;
+        } else if is_punctuation {
+            match stops.last_mut() {
+                Some(last) if after_punctuation => last.1 = end,
+                _ => stops.push((column, end)),
+            }
+            after_punctuation = true;
+        } else {
+            if subwords {
+                split_subwords(&graphemes, column, &mut stops);
+            } else {
+                stops.push((column, end));
+            }
+            after_punctuation = false;
+        }
+        column = end;
+    }
+    stops
+}
+
+/// Push the parts of a word starting at `column`, split before an uppercase
+/// letter that follows a lowercase one, before the last of a run of
+/// uppercase letters followed by a lowercase one, and around underscores
+fn split_subwords(graphemes: &[&str], column: usize, stops: &mut Vec<(usize, usize)>) {
+    let first_char = |i: usize| graphemes[i].chars().next().unwrap_or_default();
+    let mut start = None;
+    for i in 0..graphemes.len() {
+        let c = first_char(i);
+        if c == '_' && graphemes.len() > 1 {
+            if let Some(start) = start.take() {
+                stops.push((column + start, column + i));
+            }
+            continue;
+        }
+        let hump = i > 0 && c.is_uppercase() && {
+            let previous = first_char(i - 1);
+            previous.is_lowercase()
+                || (previous.is_uppercase()
+                    && i + 1 < graphemes.len()
+                    && first_char(i + 1).is_lowercase())
+        };
+        match start {
+            Some(from) if hump => {
+                stops.push((column + from, column + i));
+                start = Some(i);
+            }
+            Some(_) => {}
+            None => start = Some(i),
+        }
+    }
+    if let Some(start) = start {
+        stops.push((column + start, column + graphemes.len()));
+    }
+}
@@ -0,0 +1,543 @@
+use super::backend::{Action, Backend, Position, SearchQuery, Selection};
+
+/// How many kills the kill ring keeps
+const KILL_RING_SIZE: usize = 60;
+
+/// The commands of the Emacs profile that need more than a backend action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Start a region at the cursor, which motions then extend
+    SetMark,
+    /// Drop the region, or give up on a search
+    Cancel,
+    /// Kill the rest of the row, or the line break if there's nothing else
+    KillLine,
+    KillRegion,
+    /// Put the region in the kill ring without deleting it
+    CopyRegion,
+    Yank,
+    /// Replace the text just yanked with the kill before it
+    YankPop,
+    SearchForward,
+    SearchBackward,
+}
+
+/// What the previous command was, so kills can be joined and yanks popped
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Last {
+    #[default]
+    Other,
+    Kill,
+    /// A yank or yank pop, which put the text from `start` up to the cursor
+    Yank {
+        start: Position,
+    },
+}
+
+/// An incremental search in progress
+#[derive(Debug)]
+struct Isearch {
+    query: String,
+    backwards: bool,
+    // The selection when the search started, to go back to if it's cancelled
+    start: Selection,
+}
+
+/// The state behind the Emacs key bindings: the mark, the kill ring and
+/// incremental search. Like `Vim`, it works on a backend directly, so it
+/// doesn't need the widget.
+#[derive(Debug, Default)]
+pub struct Emacs {
+    // Whether motions extend the selection from the mark
+    mark_active: bool,
+    // Oldest first
+    kill_ring: Vec<String>,
+    // How far back from the newest kill the last yank came from
+    yank_index: usize,
+    last: Last,
+    search: Option<Isearch>,
+    // The last query searched for, which an empty search repeats
+    last_query: String,
+}
+
+impl Emacs {
+    /// The text of the incremental search in progress, if any
+    pub fn search_label(&self) -> Option<String> {
+        self.search.as_ref().map(|search| {
+            let direction = if search.backwards { " backward" } else { "" };
+            format!("I-search{direction}: {}", search.query)
+        })
+    }
+
+    /// Perform `action`, which extends the region while the mark is set.
+    /// While searching, typing edits the query instead.
+    pub fn action(&mut self, backend: &mut Backend, action: Action) {
+        if let Some(search) = &mut self.search {
+            match action {
+                Action::Insert(c) if !c.is_control() => {
+                    search.query.push(c);
+                    self.search_again(backend);
+                    return;
+                }
+                Action::Backspace => {
+                    search.query.pop();
+                    self.search_again(backend);
+                    return;
+                }
+                Action::Enter => {
+                    self.finish_search(backend);
+                    return;
+                }
+                _ => self.finish_search(backend),
+            }
+        }
+        self.last = Last::Other;
+        let action = match extended(&action) {
+            Some(extended) if self.mark_active => extended,
+            _ => action,
+        };
+        if is_edit(&action) {
+            self.mark_active = false;
+        }
+        backend.action(action);
+    }
+
+    /// Run `command`, returning any text it killed or copied for the
+    /// clipboard. A yank puts `clipboard` if it's been changed since the
+    /// last kill.
+    pub fn command(
+        &mut self,
+        backend: &mut Backend,
+        command: Command,
+        clipboard: Option<String>,
+    ) -> Option<String> {
+        if self.search.is_some() {
+            match command {
+                Command::SearchForward | Command::SearchBackward => {
+                    self.search_next(backend, command == Command::SearchBackward);
+                    return None;
+                }
+                Command::Cancel => {
+                    self.cancel_search(backend);
+                    return None;
+                }
+                _ => self.finish_search(backend),
+            }
+        }
+        let last = std::mem::take(&mut self.last);
+        match command {
+            Command::SetMark => {
+                let head = backend.selection().head;
+                backend.set_cursor(head, false);
+                self.mark_active = true;
+                None
+            }
+            Command::Cancel => {
+                self.mark_active = false;
+                backend.action(Action::Escape);
+                None
+            }
+            Command::KillLine => {
+                let head = backend.selection().head;
+                let length = backend.line_length(head.row);
+                let rest = backend.text_between(
+                    head,
+                    Position {
+                        column: length,
+                        ..head
+                    },
+                );
+                // With nothing but blanks left, the line break goes too
+                let end = if rest.trim().is_empty() && head.row + 1 < backend.line_count() {
+                    Position {
+                        row: head.row + 1,
+                        column: 0,
+                    }
+                } else {
+                    Position {
+                        column: length,
+                        ..head
+                    }
+                };
+                self.kill(backend, head, end, last)
+            }
+            Command::KillRegion => {
+                let selection = backend.selection();
+                self.kill(backend, selection.start(), selection.end(), last)
+            }
+            Command::CopyRegion => {
+                let selection = backend.selection();
+                self.mark_active = false;
+                backend.set_cursor(selection.head, false);
+                if selection.is_empty() {
+                    return None;
+                }
+                let text = backend.text_between(selection.start(), selection.end());
+                self.push_kill(text.clone());
+                Some(text)
+            }
+            Command::Yank => {
+                if let Some(text) = clipboard {
+                    if self.kill_ring.last() != Some(&text) {
+                        self.push_kill(text);
+                    }
+                }
+                let text = self.kill_ring.last()?.clone();
+                self.mark_active = false;
+                let start = backend.selection().start();
+                backend.insert(&text);
+                self.yank_index = 0;
+                self.last = Last::Yank { start };
+                None
+            }
+            Command::YankPop => {
+                let Last::Yank { start } = last else {
+                    return None;
+                };
+                self.yank_index = (self.yank_index + 1) % self.kill_ring.len();
+                let text = self.kill_ring[self.kill_ring.len() - 1 - self.yank_index].clone();
+                let end = backend.selection().head;
+                backend.set_cursor(start, false);
+                backend.set_cursor(end, true);
+                backend.insert(&text);
+                self.last = Last::Yank { start };
+                None
+            }
+            Command::SearchForward | Command::SearchBackward => {
+                self.mark_active = false;
+                self.search = Some(Isearch {
+                    query: String::new(),
+                    backwards: command == Command::SearchBackward,
+                    start: backend.selection(),
+                });
+                None
+            }
+        }
+    }
+
+    /// Delete the text from `start` up to `end` into the kill ring, adding
+    /// it to the last kill if the previous command was a kill too
+    fn kill(
+        &mut self,
+        backend: &mut Backend,
+        start: Position,
+        end: Position,
+        last: Last,
+    ) -> Option<String> {
+        self.mark_active = false;
+        self.last = Last::Kill;
+        if start == end {
+            backend.set_cursor(start, false);
+            return None;
+        }
+        let text = backend.text_between(start, end);
+        backend.set_cursor(start, false);
+        backend.set_cursor(end, true);
+        backend.action(Action::Delete);
+        match (last, self.kill_ring.last_mut()) {
+            (Last::Kill, Some(previous)) => previous.push_str(&text),
+            _ => self.push_kill(text),
+        }
+        self.kill_ring.last().cloned()
+    }
+
+    fn push_kill(&mut self, text: String) {
+        if self.kill_ring.len() == KILL_RING_SIZE {
+            self.kill_ring.remove(0);
+        }
+        self.kill_ring.push(text);
+    }
+
+    /// Select the first match of the query from where the search started
+    fn search_again(&mut self, backend: &mut Backend) {
+        let Some(search) = &self.search else {
+            return;
+        };
+        backend.set_cursor(search.start.start(), false);
+        // Like Emacs, the search is only case sensitive once the query has
+        // capitals in it
+        let query = SearchQuery {
+            text: search.query.clone(),
+            case_sensitive: search.query.chars().any(char::is_uppercase),
+            ..SearchQuery::default()
+        };
+        if backend.set_search(&query).is_ok() && search.backwards {
+            backend.action(Action::FindPrevious);
+        }
+    }
+
+    /// Move to the next match, or the previous one when going `backwards`.
+    /// An empty query searches for the last one again.
+    fn search_next(&mut self, backend: &mut Backend, backwards: bool) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        search.backwards = backwards;
+        if search.query.is_empty() {
+            search.query = self.last_query.clone();
+            self.search_again(backend);
+        } else {
+            backend.action(if backwards {
+                Action::FindPrevious
+            } else {
+                Action::FindNext
+            });
+        }
+    }
+
+    /// End the search, leaving the cursor at the match
+    fn finish_search(&mut self, backend: &mut Backend) {
+        let Some(search) = self.search.take() else {
+            return;
+        };
+        if !search.query.is_empty() {
+            self.last_query = search.query;
+        }
+        let selection = backend.selection();
+        let cursor = if search.backwards {
+            selection.start()
+        } else {
+            selection.end()
+        };
+        backend.set_cursor(cursor, false);
+        backend.clear_search();
+    }
+
+    /// End the search, going back to where it started
+    fn cancel_search(&mut self, backend: &mut Backend) {
+        let Some(search) = self.search.take() else {
+            return;
+        };
+        backend.set_cursor(search.start.anchor, false);
+        backend.set_cursor(search.start.head, true);
+        backend.clear_search();
+    }
+}
+
+/// The version of a motion that extends the selection
+fn extended(action: &Action) -> Option<Action> {
+    let extended = match action {
+        Action::Up => Action::SelectUp,
+        Action::Down => Action::SelectDown,
+        Action::Left => Action::SelectLeft,
+        Action::Right => Action::SelectRight,
+        Action::Home => Action::SelectHome,
+        Action::End => Action::SelectEnd,
+        Action::PageUp => Action::SelectPageUp,
+        Action::PageDown => Action::SelectPageDown,
+        Action::DocumentStart => Action::SelectDocumentStart,
+        Action::DocumentEnd => Action::SelectDocumentEnd,
+        Action::WordLeft => Action::SelectWordLeft,
+        Action::WordRight => Action::SelectWordRight,
+        Action::SubwordLeft => Action::SelectSubwordLeft,
+        Action::SubwordRight => Action::SelectSubwordRight,
+        _ => return None,
+    };
+    Some(extended)
+}
+
+/// Whether `action` changes the text, which deactivates the mark
+fn is_edit(action: &Action) -> bool {
+    matches!(
+        action,
+        Action::Insert(_)
+            | Action::Delete
+            | Action::Enter
+            | Action::Backspace
+            | Action::Undo
+            | Action::Redo
+            | Action::Indent
+            | Action::Dedent
+            | Action::DeleteWordBackward
+            | Action::DeleteWordForward
+            | Action::DeleteSubwordBackward
+            | Action::DeleteSubwordForward
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Type `text` into the search, or the document if there's none
+    fn typed(emacs: &mut Emacs, backend: &mut Backend, text: &str) {
+        for c in text.chars() {
+            emacs.action(backend, Action::Insert(c));
+        }
+    }
+
+    fn selected(backend: &Backend) -> String {
+        let selection = backend.selection();
+        backend.text_between(selection.start(), selection.end())
+    }
+
+    fn cursor(backend: &Backend) -> (usize, usize) {
+        let head = backend.selection().head;
+        (head.row, head.column)
+    }
+
+    #[test]
+    fn consecutive_kills_are_joined() {
+        let mut backend = Backend::from("one\ntwo\nthree");
+        let mut emacs = Emacs::default();
+        let kill = |emacs: &mut Emacs, backend: &mut Backend| {
+            emacs.command(backend, Command::KillLine, None)
+        };
+        assert_eq!(kill(&mut emacs, &mut backend), Some("one".to_string()));
+        // With nothing left on the row, the line break is killed
+        assert_eq!(kill(&mut emacs, &mut backend), Some("one\n".to_string()));
+        assert_eq!(kill(&mut emacs, &mut backend), Some("one\ntwo".to_string()));
+        assert_eq!(backend.content(), "\nthree");
+        emacs.command(&mut backend, Command::Yank, None);
+        assert_eq!(backend.content(), "one\ntwo\nthree");
+
+        // Anything in between starts a kill of its own
+        let mut backend = Backend::from("one two");
+        let mut emacs = Emacs::default();
+        emacs.action(&mut backend, Action::WordRight);
+        kill(&mut emacs, &mut backend);
+        emacs.action(&mut backend, Action::Home);
+        kill(&mut emacs, &mut backend);
+        assert_eq!(emacs.kill_ring, [" two", "one"].map(String::from));
+    }
+
+    #[test]
+    fn yank_pop_rotates_through_the_kill_ring() {
+        let mut backend = Backend::from("one\ntwo\n");
+        let mut emacs = Emacs::default();
+        emacs.command(&mut backend, Command::KillLine, None);
+        emacs.action(&mut backend, Action::Down);
+        emacs.command(&mut backend, Command::KillLine, None);
+        assert_eq!(backend.content(), "\n\n");
+
+        emacs.command(&mut backend, Command::Yank, None);
+        assert_eq!(backend.content(), "\ntwo\n");
+        emacs.command(&mut backend, Command::YankPop, None);
+        assert_eq!(backend.content(), "\none\n");
+        // Past the oldest kill it wraps around to the newest
+        emacs.command(&mut backend, Command::YankPop, None);
+        assert_eq!(backend.content(), "\ntwo\n");
+        assert_eq!(cursor(&backend), (1, 3));
+
+        // Only right after a yank
+        emacs.action(&mut backend, Action::Left);
+        emacs.command(&mut backend, Command::YankPop, None);
+        assert_eq!(backend.content(), "\ntwo\n");
+    }
+
+    #[test]
+    fn yank_takes_a_changed_clipboard() {
+        let mut backend = Backend::from("");
+        let mut emacs = Emacs::default();
+        assert_eq!(emacs.command(&mut backend, Command::Yank, None), None);
+        assert_eq!(backend.content(), "");
+        let clipboard = Some("copied".to_string());
+        emacs.command(&mut backend, Command::Yank, clipboard.clone());
+        emacs.command(&mut backend, Command::Yank, clipboard);
+        assert_eq!(backend.content(), "copiedcopied");
+        assert_eq!(emacs.kill_ring.len(), 1);
+    }
+
+    #[test]
+    fn mark_and_region() {
+        let mut backend = Backend::from("one two three");
+        let mut emacs = Emacs::default();
+        emacs.command(&mut backend, Command::SetMark, None);
+        for _ in 0..3 {
+            emacs.action(&mut backend, Action::Right);
+        }
+        assert_eq!(selected(&backend), "one");
+        let killed = emacs.command(&mut backend, Command::KillRegion, None);
+        assert_eq!(killed, Some("one".to_string()));
+        assert_eq!(backend.content(), " two three");
+
+        // Copying leaves the text and drops the region
+        emacs.command(&mut backend, Command::SetMark, None);
+        emacs.action(&mut backend, Action::End);
+        let copied = emacs.command(&mut backend, Command::CopyRegion, None);
+        assert_eq!(copied, Some(" two three".to_string()));
+        assert_eq!(backend.content(), " two three");
+        emacs.action(&mut backend, Action::Left);
+        assert_eq!(selected(&backend), "");
+
+        // So do C-g and editing
+        emacs.command(&mut backend, Command::SetMark, None);
+        emacs.command(&mut backend, Command::Cancel, None);
+        emacs.action(&mut backend, Action::Left);
+        assert_eq!(selected(&backend), "");
+        emacs.command(&mut backend, Command::SetMark, None);
+        typed(&mut emacs, &mut backend, "!");
+        emacs.action(&mut backend, Action::Left);
+        assert_eq!(selected(&backend), "");
+        assert_eq!(backend.content(), " two thr!ee");
+    }
+
+    #[test]
+    fn incremental_search_wraps_around() {
+        let mut backend = Backend::from("abc abc abc");
+        let mut emacs = Emacs::default();
+        emacs.command(&mut backend, Command::SearchForward, None);
+        typed(&mut emacs, &mut backend, "bc");
+        assert_eq!(emacs.search_label().as_deref(), Some("I-search: bc"));
+        assert_eq!(cursor(&backend), (0, 3));
+        emacs.command(&mut backend, Command::SearchForward, None);
+        assert_eq!(cursor(&backend), (0, 7));
+        emacs.command(&mut backend, Command::SearchForward, None);
+        emacs.command(&mut backend, Command::SearchForward, None);
+        assert_eq!(cursor(&backend), (0, 3));
+        // Typing edits the query, searching again from the start
+        emacs.action(&mut backend, Action::Backspace);
+        assert_eq!(selected(&backend), "b");
+        assert_eq!(backend.content(), "abc abc abc");
+
+        // Enter leaves the cursor after the match
+        emacs.action(&mut backend, Action::Enter);
+        assert_eq!(emacs.search_label(), None);
+        assert_eq!(cursor(&backend), (0, 2));
+        assert_eq!(selected(&backend), "");
+    }
+
+    #[test]
+    fn incremental_search_backward() {
+        let mut backend = Backend::from("abc abc abc");
+        let mut emacs = Emacs::default();
+        emacs.action(&mut backend, Action::End);
+        emacs.command(&mut backend, Command::SearchBackward, None);
+        typed(&mut emacs, &mut backend, "abc");
+        assert_eq!(cursor(&backend), (0, 11));
+        emacs.command(&mut backend, Command::SearchBackward, None);
+        emacs.command(&mut backend, Command::SearchBackward, None);
+        emacs.command(&mut backend, Command::SearchBackward, None);
+        assert_eq!(cursor(&backend), (0, 11));
+        emacs.command(&mut backend, Command::SearchBackward, None);
+        // Finishing leaves the cursor before the match
+        emacs.action(&mut backend, Action::Right);
+        assert_eq!(cursor(&backend), (0, 5));
+    }
+
+    #[test]
+    fn incremental_search_cancel_and_repeat() {
+        let mut backend = Backend::from("one Two two");
+        let mut emacs = Emacs::default();
+        emacs.action(&mut backend, Action::Right);
+        emacs.command(&mut backend, Command::SearchForward, None);
+        typed(&mut emacs, &mut backend, "two");
+        // Lowercase matches either case
+        assert_eq!(cursor(&backend), (0, 7));
+        emacs.command(&mut backend, Command::Cancel, None);
+        assert_eq!(emacs.search_label(), None);
+        assert_eq!(cursor(&backend), (0, 1));
+
+        // Capitals make it case sensitive
+        emacs.command(&mut backend, Command::SearchForward, None);
+        typed(&mut emacs, &mut backend, "Two");
+        emacs.action(&mut backend, Action::Enter);
+        assert_eq!(cursor(&backend), (0, 7));
+
+        // An empty search repeats the last one
+        emacs.action(&mut backend, Action::DocumentStart);
+        emacs.command(&mut backend, Command::SearchForward, None);
+        emacs.command(&mut backend, Command::SearchForward, None);
+        assert_eq!(selected(&backend), "Two");
+    }
+}
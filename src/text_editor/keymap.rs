@@ -0,0 +1,635 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use iced::keyboard::{KeyCode, Modifiers};
+
+use super::backend::Action;
+use super::emacs;
+
+pub mod config;
+
+/// A set of key bindings for the editor, chosen when it starts
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Keymap {
+    #[default]
+    Standard,
+    /// Emacs bindings, with a mark, a kill ring and incremental search.
+    /// Keys it doesn't bind, like the arrows, work as they do in `Standard`.
+    Emacs,
+}
+
+impl FromStr for Keymap {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "standard" => Ok(Keymap::Standard),
+            "emacs" => Ok(Keymap::Emacs),
+            _ => Err(format!(
+                "Unknown keymap \"{name}\", expected \"standard\" or \"emacs\""
+            )),
+        }
+    }
+}
+
+/// What a key press does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Action(Action),
+    Copy,
+    Cut,
+    Paste,
+    Emacs(emacs::Command),
+}
+
+impl Command {
+    /// Every command that can be bound to a key, by the name it has in the
+    /// keymap config
+    pub const ALL: &'static [(&'static str, Command)] = &[
+        ("up", Command::Action(Action::Up)),
+        ("down", Command::Action(Action::Down)),
+        ("left", Command::Action(Action::Left)),
+        ("right", Command::Action(Action::Right)),
+        ("home", Command::Action(Action::Home)),
+        ("end", Command::Action(Action::End)),
+        ("page_up", Command::Action(Action::PageUp)),
+        ("page_down", Command::Action(Action::PageDown)),
+        ("document_start", Command::Action(Action::DocumentStart)),
+        ("document_end", Command::Action(Action::DocumentEnd)),
+        ("word_left", Command::Action(Action::WordLeft)),
+        ("word_right", Command::Action(Action::WordRight)),
+        ("subword_left", Command::Action(Action::SubwordLeft)),
+        ("subword_right", Command::Action(Action::SubwordRight)),
+        ("select_up", Command::Action(Action::SelectUp)),
+        ("select_down", Command::Action(Action::SelectDown)),
+        ("select_left", Command::Action(Action::SelectLeft)),
+        ("select_right", Command::Action(Action::SelectRight)),
+        ("select_home", Command::Action(Action::SelectHome)),
+        ("select_end", Command::Action(Action::SelectEnd)),
+        ("select_page_up", Command::Action(Action::SelectPageUp)),
+        ("select_page_down", Command::Action(Action::SelectPageDown)),
+        (
+            "select_document_start",
+            Command::Action(Action::SelectDocumentStart),
+        ),
+        (
+            "select_document_end",
+            Command::Action(Action::SelectDocumentEnd),
+        ),
+        ("select_word_left", Command::Action(Action::SelectWordLeft)),
+        (
+            "select_word_right",
+            Command::Action(Action::SelectWordRight),
+        ),
+        (
+            "select_subword_left",
+            Command::Action(Action::SelectSubwordLeft),
+        ),
+        (
+            "select_subword_right",
+            Command::Action(Action::SelectSubwordRight),
+        ),
+        ("select_all", Command::Action(Action::SelectAll)),
+        ("expand_selection", Command::Action(Action::ExpandSelection)),
+        ("shrink_selection", Command::Action(Action::ShrinkSelection)),
+        ("matching_bracket", Command::Action(Action::MatchingBracket)),
+        ("add_cursor_up", Command::Action(Action::AddCursorUp)),
+        ("add_cursor_down", Command::Action(Action::AddCursorDown)),
+        (
+            "add_next_occurrence",
+            Command::Action(Action::AddNextOccurrence),
+        ),
+        ("escape", Command::Action(Action::Escape)),
+        ("enter", Command::Action(Action::Enter)),
+        ("backspace", Command::Action(Action::Backspace)),
+        ("delete", Command::Action(Action::Delete)),
+        (
+            "delete_word_backward",
+            Command::Action(Action::DeleteWordBackward),
+        ),
+        (
+            "delete_word_forward",
+            Command::Action(Action::DeleteWordForward),
+        ),
+        (
+            "delete_subword_backward",
+            Command::Action(Action::DeleteSubwordBackward),
+        ),
+        (
+            "delete_subword_forward",
+            Command::Action(Action::DeleteSubwordForward),
+        ),
+        ("indent", Command::Action(Action::Indent)),
+        ("dedent", Command::Action(Action::Dedent)),
+        ("undo", Command::Action(Action::Undo)),
+        ("redo", Command::Action(Action::Redo)),
+        ("find_next", Command::Action(Action::FindNext)),
+        ("find_previous", Command::Action(Action::FindPrevious)),
+        ("copy", Command::Copy),
+        ("cut", Command::Cut),
+        ("paste", Command::Paste),
+        ("set_mark", Command::Emacs(emacs::Command::SetMark)),
+        ("cancel", Command::Emacs(emacs::Command::Cancel)),
+        ("kill_line", Command::Emacs(emacs::Command::KillLine)),
+        ("kill_region", Command::Emacs(emacs::Command::KillRegion)),
+        ("copy_region", Command::Emacs(emacs::Command::CopyRegion)),
+        ("yank", Command::Emacs(emacs::Command::Yank)),
+        ("yank_pop", Command::Emacs(emacs::Command::YankPop)),
+        (
+            "search_forward",
+            Command::Emacs(emacs::Command::SearchForward),
+        ),
+        (
+            "search_backward",
+            Command::Emacs(emacs::Command::SearchBackward),
+        ),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(_, command)| command == self)
+            .map_or("", |(name, _)| name)
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|(command, _)| *command == name)
+            .map(|(_, command)| *command)
+            .ok_or_else(|| format!("Unknown command \"{name}\""))
+    }
+}
+
+/// A key pressed along with modifiers, like `ctrl+shift+k`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub key_code: KeyCode,
+    pub modifiers: Modifiers,
+}
+
+/// The names of the keys that can be part of a chord
+const KEYS: &[(&str, KeyCode)] = &[
+    ("a", KeyCode::A),
+    ("b", KeyCode::B),
+    ("c", KeyCode::C),
+    ("d", KeyCode::D),
+    ("e", KeyCode::E),
+    ("f", KeyCode::F),
+    ("g", KeyCode::G),
+    ("h", KeyCode::H),
+    ("i", KeyCode::I),
+    ("j", KeyCode::J),
+    ("k", KeyCode::K),
+    ("l", KeyCode::L),
+    ("m", KeyCode::M),
+    ("n", KeyCode::N),
+    ("o", KeyCode::O),
+    ("p", KeyCode::P),
+    ("q", KeyCode::Q),
+    ("r", KeyCode::R),
+    ("s", KeyCode::S),
+    ("t", KeyCode::T),
+    ("u", KeyCode::U),
+    ("v", KeyCode::V),
+    ("w", KeyCode::W),
+    ("x", KeyCode::X),
+    ("y", KeyCode::Y),
+    ("z", KeyCode::Z),
+    ("0", KeyCode::Key0),
+    ("1", KeyCode::Key1),
+    ("2", KeyCode::Key2),
+    ("3", KeyCode::Key3),
+    ("4", KeyCode::Key4),
+    ("5", KeyCode::Key5),
+    ("6", KeyCode::Key6),
+    ("7", KeyCode::Key7),
+    ("8", KeyCode::Key8),
+    ("9", KeyCode::Key9),
+    ("f1", KeyCode::F1),
+    ("f2", KeyCode::F2),
+    ("f3", KeyCode::F3),
+    ("f4", KeyCode::F4),
+    ("f5", KeyCode::F5),
+    ("f6", KeyCode::F6),
+    ("f7", KeyCode::F7),
+    ("f8", KeyCode::F8),
+    ("f9", KeyCode::F9),
+    ("f10", KeyCode::F10),
+    ("f11", KeyCode::F11),
+    ("f12", KeyCode::F12),
+    ("escape", KeyCode::Escape),
+    ("enter", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("space", KeyCode::Space),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("-", KeyCode::Minus),
+    ("=", KeyCode::Equals),
+    ("[", KeyCode::LBracket),
+    ("]", KeyCode::RBracket),
+    ("\\", KeyCode::Backslash),
+    (";", KeyCode::Semicolon),
+    ("'", KeyCode::Apostrophe),
+    ("`", KeyCode::Grave),
+    (",", KeyCode::Comma),
+    (".", KeyCode::Period),
+    ("/", KeyCode::Slash),
+];
+
+/// The names of the modifiers, in the order they're written in a chord
+const MODIFIERS: &[(&str, Modifiers)] = &[
+    ("ctrl", Modifiers::CTRL),
+    ("alt", Modifiers::ALT),
+    ("shift", Modifiers::SHIFT),
+    ("super", Modifiers::LOGO),
+];
+
+impl Chord {
+    /// Whether the key is a modifier on its own, which is held down for a
+    /// chord rather than being part of it
+    pub fn is_modifier(&self) -> bool {
+        matches!(
+            self.key_code,
+            KeyCode::LShift
+                | KeyCode::RShift
+                | KeyCode::LControl
+                | KeyCode::RControl
+                | KeyCode::LAlt
+                | KeyCode::RAlt
+                | KeyCode::LWin
+                | KeyCode::RWin
+        )
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.to_lowercase();
+        // The key comes last, and may be `+` itself
+        let (modifiers, key) = match text.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None => text.rsplit_once('+').unwrap_or(("", &text)),
+        };
+        let mut chord = Chord {
+            key_code: match key {
+                "+" => KeyCode::Plus,
+                "esc" => KeyCode::Escape,
+                "return" => KeyCode::Enter,
+                "del" => KeyCode::Delete,
+                _ => KEYS
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, key_code)| *key_code)
+                    .ok_or_else(|| format!("Unknown key \"{key}\""))?,
+            },
+            modifiers: Modifiers::empty(),
+        };
+        for modifier in modifiers.split('+').filter(|name| !name.is_empty()) {
+            chord.modifiers |= match modifier {
+                "control" => Modifiers::CTRL,
+                "meta" => Modifiers::ALT,
+                "cmd" | "logo" => Modifiers::LOGO,
+                _ => MODIFIERS
+                    .iter()
+                    .find(|(name, _)| *name == modifier)
+                    .map(|(_, modifier)| *modifier)
+                    .ok_or_else(|| format!("Unknown modifier \"{modifier}\""))?,
+            };
+        }
+        Ok(chord)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, modifier) in MODIFIERS {
+            if self.modifiers.contains(*modifier) {
+                write!(f, "{name}+")?;
+            }
+        }
+        let key = match self.key_code {
+            KeyCode::Plus => "+",
+            key_code => KEYS
+                .iter()
+                .find(|(_, key)| *key == key_code)
+                .map_or("?", |(name, _)| name),
+        };
+        f.write_str(key)
+    }
+}
+
+/// The app's own shortcuts, by the title the palette gives them. They only
+/// work while the editor leaves the chord alone, so binding one hides it.
+pub const APP_SHORTCUTS: &[(&str, &str)] = &[
+    ("Save", "ctrl+s"),
+    ("Save As", "ctrl+shift+s"),
+    ("Close Buffer", "ctrl+w"),
+    ("Close Pane", "ctrl+shift+w"),
+    ("Split Right", "ctrl+\\"),
+    ("Split Down", "ctrl+alt+\\"),
+    ("Toggle File Tree", "ctrl+b"),
+    ("New Buffer", "ctrl+n"),
+    ("Next Buffer", "ctrl+tab"),
+    ("Previous Buffer", "ctrl+shift+tab"),
+    ("Find", "ctrl+f"),
+    ("Go to File", "ctrl+p"),
+    ("Command Palette", "ctrl+shift+p"),
+    ("Focus Left Pane", "alt+left"),
+    ("Focus Right Pane", "alt+right"),
+    ("Focus Pane Above", "alt+up"),
+    ("Focus Pane Below", "alt+down"),
+];
+
+/// The title of the app shortcut on `chord`, if there is one
+pub fn app_shortcut(chord: Chord) -> Option<&'static str> {
+    APP_SHORTCUTS
+        .iter()
+        .find(|(_, keys)| keys.parse() == Ok(chord))
+        .map(|(title, _)| *title)
+}
+
+/// Key bindings from the keymap config, which take precedence over the
+/// keymap's own. Each binds a sequence of one or more chords.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Bindings {
+    // `None` takes away the keymap's own binding
+    sequences: HashMap<Vec<Chord>, Option<Command>>,
+}
+
+/// What the chords typed so far do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    Command(Command),
+    /// The start of a sequence, which waits for the next chord
+    Prefix,
+    Unbound,
+}
+
+impl Keymap {
+    /// The command bound to a key press, if any
+    pub fn command(self, key_code: KeyCode, modifiers: Modifiers) -> Option<Command> {
+        match self {
+            Keymap::Standard => standard(key_code, modifiers),
+            Keymap::Emacs => emacs(key_code, modifiers).or_else(|| standard(key_code, modifiers)),
+        }
+    }
+
+    /// What the `chords` typed so far do, with `bindings` taking precedence
+    /// over the keymap's own
+    pub fn lookup(self, bindings: &Bindings, chords: &[Chord]) -> Lookup {
+        if let Some(Some(command)) = bindings.sequences.get(chords) {
+            return Lookup::Command(*command);
+        }
+        let is_prefix = bindings
+            .sequences
+            .keys()
+            .any(|sequence| sequence.len() > chords.len() && sequence.starts_with(chords));
+        match chords {
+            // A chord bound to `none` can still start a longer sequence
+            _ if is_prefix => Lookup::Prefix,
+            _ if bindings.sequences.contains_key(chords) => Lookup::Unbound,
+            [chord] => self
+                .command(chord.key_code, chord.modifiers)
+                .map_or(Lookup::Unbound, Lookup::Command),
+            _ => Lookup::Unbound,
+        }
+    }
+
+    /// Every sequence of keys that runs a command, with `bindings` taking
+    /// precedence over the keymap's own. Shorter sequences come first, and
+    /// chords with fewer modifiers before those with more.
+    pub fn key_sequences(self, bindings: &Bindings) -> Vec<(Vec<Chord>, Command)> {
+        let mut sequences: Vec<(Vec<Chord>, Command)> = bindings
+            .sequences
+            .iter()
+            .filter_map(|(sequence, command)| Some((sequence.clone(), (*command)?)))
+            .collect();
+        // The map has no order of its own
+        sequences.sort_by_cached_key(|(sequence, _)| {
+            sequence.iter().map(Chord::to_string).collect::<Vec<_>>()
+        });
+        for set in 0..1 << MODIFIERS.len() {
+            let modifiers = MODIFIERS
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| set & 1 << i != 0)
+                .fold(Modifiers::empty(), |modifiers, (_, (_, modifier))| {
+                    modifiers | *modifier
+                });
+            for (_, key_code) in KEYS {
+                let chord = Chord {
+                    key_code: *key_code,
+                    modifiers,
+                };
+                if bindings.sequences.contains_key([chord].as_slice()) {
+                    continue;
+                }
+                if let Lookup::Command(command) = self.lookup(bindings, &[chord]) {
+                    sequences.push((vec![chord], command));
+                }
+            }
+        }
+        sequences.sort_by_key(|(sequence, _)| {
+            let modifiers: u32 = sequence
+                .iter()
+                .map(|chord| chord.modifiers.bits().count_ones())
+                .sum();
+            (sequence.len(), modifiers)
+        });
+        sequences
+    }
+}
+
+fn standard(key_code: KeyCode, modifiers: Modifiers) -> Option<Command> {
+    let (control, alt, shift) = (modifiers.control(), modifiers.alt(), modifiers.shift());
+    let action = match key_code {
+        // Alt with an arrow on its own is left for moving between panes
+        KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down
+            if alt && !control && !shift =>
+        {
+            return None
+        }
+        KeyCode::Left if alt && shift && !control => Action::ShrinkSelection,
+        KeyCode::Right if alt && shift && !control => Action::ExpandSelection,
+        // Ctrl moves by word, and Ctrl+Alt by camelCase or snake_case part
+        KeyCode::Left => match (control, alt, shift) {
+            (true, true, true) => Action::SelectSubwordLeft,
+            (true, true, false) => Action::SubwordLeft,
+            (true, false, true) => Action::SelectWordLeft,
+            (true, false, false) => Action::WordLeft,
+            (false, _, true) => Action::SelectLeft,
+            (false, _, false) => Action::Left,
+        },
+        KeyCode::Right => match (control, alt, shift) {
+            (true, true, true) => Action::SelectSubwordRight,
+            (true, true, false) => Action::SubwordRight,
+            (true, false, true) => Action::SelectWordRight,
+            (true, false, false) => Action::WordRight,
+            (false, _, true) => Action::SelectRight,
+            (false, _, false) => Action::Right,
+        },
+        KeyCode::Up if control && alt => Action::AddCursorUp,
+        KeyCode::Down if control && alt => Action::AddCursorDown,
+        KeyCode::Up if shift => Action::SelectUp,
+        KeyCode::Up => Action::Up,
+        KeyCode::Down if shift => Action::SelectDown,
+        KeyCode::Down => Action::Down,
+        KeyCode::PageUp if shift => Action::SelectPageUp,
+        KeyCode::PageUp => Action::PageUp,
+        KeyCode::PageDown if shift => Action::SelectPageDown,
+        KeyCode::PageDown => Action::PageDown,
+        KeyCode::Home => match (control, shift) {
+            (true, true) => Action::SelectDocumentStart,
+            (true, false) => Action::DocumentStart,
+            (false, true) => Action::SelectHome,
+            (false, false) => Action::Home,
+        },
+        KeyCode::End => match (control, shift) {
+            (true, true) => Action::SelectDocumentEnd,
+            (true, false) => Action::DocumentEnd,
+            (false, true) => Action::SelectEnd,
+            (false, false) => Action::End,
+        },
+        KeyCode::Escape => Action::Escape,
+        KeyCode::Enter => Action::Enter,
+        KeyCode::Backspace => match (control, alt) {
+            (true, true) => Action::DeleteSubwordBackward,
+            (true, false) => Action::DeleteWordBackward,
+            (false, _) => Action::Backspace,
+        },
+        KeyCode::Delete => match (control, alt) {
+            (true, true) => Action::DeleteSubwordForward,
+            (true, false) => Action::DeleteWordForward,
+            (false, _) => Action::Delete,
+        },
+        // Ctrl+Tab is left for switching buffers
+        KeyCode::Tab if control => return None,
+        KeyCode::Tab if shift => Action::Dedent,
+        KeyCode::Tab => Action::Indent,
+        KeyCode::Backslash if control && shift => Action::MatchingBracket,
+        KeyCode::A if control => Action::SelectAll,
+        KeyCode::D if control => Action::AddNextOccurrence,
+        KeyCode::C if control => return Some(Command::Copy),
+        KeyCode::X if control => return Some(Command::Cut),
+        KeyCode::V if control => return Some(Command::Paste),
+        KeyCode::Z if control && shift => Action::Redo,
+        KeyCode::Z if control => Action::Undo,
+        _ => return None,
+    };
+    Some(Command::Action(action))
+}
+
+/// The Emacs bindings, where Alt is Meta
+fn emacs(key_code: KeyCode, modifiers: Modifiers) -> Option<Command> {
+    use emacs::Command as Emacs;
+
+    let (control, meta) = (modifiers.control(), modifiers.alt());
+    let action = match key_code {
+        KeyCode::A if control => Action::Home,
+        KeyCode::E if control => Action::End,
+        KeyCode::F if control => Action::Right,
+        KeyCode::B if control => Action::Left,
+        KeyCode::N if control => Action::Down,
+        // Ctrl+Shift+P is left for the command palette
+        KeyCode::P if control && !modifiers.shift() => Action::Up,
+        KeyCode::V if control => Action::PageDown,
+        KeyCode::D if control => Action::Delete,
+        KeyCode::Slash | KeyCode::Underline if control => Action::Undo,
+        KeyCode::F if meta => Action::WordRight,
+        KeyCode::B if meta => Action::WordLeft,
+        KeyCode::V if meta => Action::PageUp,
+        KeyCode::D if meta => Action::DeleteWordForward,
+        KeyCode::Backspace if meta => Action::DeleteWordBackward,
+        // M-< and M->
+        KeyCode::Comma if meta && modifiers.shift() => Action::DocumentStart,
+        KeyCode::Period if meta && modifiers.shift() => Action::DocumentEnd,
+        KeyCode::Space if control => return Some(Command::Emacs(Emacs::SetMark)),
+        KeyCode::G if control => return Some(Command::Emacs(Emacs::Cancel)),
+        KeyCode::K if control => return Some(Command::Emacs(Emacs::KillLine)),
+        // Ctrl+Shift+W is left for closing panes
+        KeyCode::W if control && !modifiers.shift() => {
+            return Some(Command::Emacs(Emacs::KillRegion))
+        }
+        KeyCode::W if meta => return Some(Command::Emacs(Emacs::CopyRegion)),
+        KeyCode::Y if control => return Some(Command::Emacs(Emacs::Yank)),
+        KeyCode::Y if meta => return Some(Command::Emacs(Emacs::YankPop)),
+        // Ctrl+Shift+S is left for Save As
+        KeyCode::S if control && !modifiers.shift() => {
+            return Some(Command::Emacs(Emacs::SearchForward))
+        }
+        KeyCode::R if control => return Some(Command::Emacs(Emacs::SearchBackward)),
+        _ => return None,
+    };
+    Some(Command::Action(action))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(text: &str) -> Chord {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_chords() {
+        assert_eq!(
+            chord("ctrl+shift+k"),
+            Chord {
+                key_code: KeyCode::K,
+                modifiers: Modifiers::CTRL | Modifiers::SHIFT,
+            }
+        );
+        assert_eq!(chord("Control+Meta+Del"), chord("ctrl+alt+delete"));
+        assert_eq!(chord("cmd+esc"), chord("super+escape"));
+        assert_eq!(chord("ctrl++").key_code, KeyCode::Plus);
+        assert_eq!(chord("f3").modifiers, Modifiers::empty());
+        assert_eq!(
+            "ctrl+foo".parse::<Chord>(),
+            Err("Unknown key \"foo\"".to_string())
+        );
+        assert_eq!(
+            "hyper+a".parse::<Chord>(),
+            Err("Unknown modifier \"hyper\"".to_string())
+        );
+    }
+
+    #[test]
+    fn chords_round_trip() {
+        for text in ["ctrl+alt+shift+super+a", "shift+f3", "ctrl+\\", "alt+left"] {
+            assert_eq!(chord(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn freed_chord_starts_a_sequence() {
+        let (bindings, errors) = config::parse(
+            "[bindings]\n\"ctrl+x\" = \"none\"\n\"ctrl+x ctrl+s\" = \"undo\"\n",
+            Keymap::Standard,
+        );
+        assert_eq!(errors, []);
+        let keymap = Keymap::Standard;
+        assert_eq!(keymap.lookup(&bindings, &[chord("ctrl+x")]), Lookup::Prefix);
+        assert_eq!(
+            keymap.lookup(&bindings, &[chord("ctrl+x"), chord("ctrl+s")]),
+            Lookup::Command(Command::Action(Action::Undo))
+        );
+    }
+}
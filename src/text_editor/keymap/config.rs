@@ -0,0 +1,371 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use iced::futures::{channel::mpsc, SinkExt, StreamExt};
+use iced::{subscription, Subscription};
+use notify::{RecursiveMode, Watcher};
+use serde::Deserialize;
+use toml::Spanned;
+
+use super::{app_shortcut, Bindings, Chord, Command, Keymap};
+
+const FILE_NAME: &str = "keymap.toml";
+
+/// The keymap config, `text_editor/keymap.toml` in the XDG config directory.
+/// It binds sequences of chords to commands by name, with `none` taking a
+/// binding away. A chord the keymap or the app already uses has to be taken
+/// away before it can start a longer sequence:
+///
+/// ```toml
+/// [bindings]
+/// "ctrl+shift+k" = "kill_line"
+/// "ctrl+x" = "none"
+/// "ctrl+x ctrl+x" = "cut"
+/// "ctrl+d" = "none"
+/// ```
+pub fn path() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("text_editor").join(FILE_NAME))
+}
+
+/// Read the bindings from the keymap config, which go on top of `keymap`'s
+/// own, along with any problems in it. There are no bindings if the file
+/// doesn't exist.
+pub fn load(keymap: Keymap) -> io::Result<(Bindings, Vec<Error>)> {
+    let Some(path) = path() else {
+        return Ok(Default::default());
+    };
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(parse(&text, keymap)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Default::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// A problem with the keymap config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// The line it's on, counting from 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{FILE_NAME} line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default)]
+    bindings: BTreeMap<Spanned<String>, Spanned<String>>,
+}
+
+/// Parse the text of the keymap config, as bindings on top of `keymap`.
+/// Bindings that can't be made sense of, that conflict with one earlier in
+/// the file, or that would quietly hide one of `keymap`'s or the app's, are
+/// left out and reported instead.
+pub fn parse(text: &str, keymap: Keymap) -> (Bindings, Vec<Error>) {
+    let line_of = |index: usize| text[..index.min(text.len())].matches('\n').count() + 1;
+    let file: File = match toml::from_str(text) {
+        Ok(file) => file,
+        Err(e) => {
+            let error = Error {
+                line: e.span().map_or(1, |span| line_of(span.start)),
+                message: e.message().to_string(),
+            };
+            return (Bindings::default(), vec![error]);
+        }
+    };
+
+    let mut errors = Vec::new();
+    // The line each sequence was bound on
+    let mut lines: HashMap<Vec<Chord>, usize> = HashMap::new();
+    let mut bindings = Bindings::default();
+    // In the order they're written, so conflicts are blamed on the later one
+    let mut entries: Vec<_> = file.bindings.into_iter().collect();
+    entries.sort_by_key(|(keys, _)| keys.span().start);
+    // Chords taken away with `none`, which are free to start sequences
+    let freed: HashSet<Chord> = entries
+        .iter()
+        .filter(|(_, command)| command.get_ref() == "none")
+        .filter_map(|(keys, _)| keys.get_ref().parse().ok())
+        .collect();
+    for (keys, command) in entries {
+        let line = line_of(keys.span().start);
+        let mut error = |message: String| errors.push(Error { line, message });
+        let sequence: Result<Vec<Chord>, String> =
+            keys.get_ref().split_whitespace().map(str::parse).collect();
+        let sequence = match sequence {
+            Ok(sequence) if sequence.is_empty() => {
+                error("No keys to bind".to_string());
+                continue;
+            }
+            Ok(sequence) => sequence,
+            Err(message) => {
+                error(message);
+                continue;
+            }
+        };
+        let command = match command.get_ref().as_str() {
+            "none" => None,
+            name => match name.parse::<Command>() {
+                Ok(command) => Some(command),
+                Err(message) => {
+                    error(message);
+                    continue;
+                }
+            },
+        };
+
+        // A sequence that starts with another can never be finished, unless
+        // the shorter one only takes a binding away
+        let overlapping = lines.iter().find(|(bound, _)| {
+            let shorter = if bound.len() < sequence.len() {
+                bound.as_slice()
+            } else {
+                &sequence
+            };
+            bound.len() != sequence.len()
+                && (bound.starts_with(&sequence) || sequence.starts_with(bound))
+                && !(shorter.len() == 1 && freed.contains(&shorter[0]))
+        });
+        if let Some((bound, bound_line)) = overlapping {
+            error(format!(
+                "\"{}\" conflicts with \"{}\" on line {bound_line}, as one starts the other",
+                show(&sequence),
+                show(bound),
+            ));
+            continue;
+        }
+        if let Some(message) = hidden(keymap, &sequence, command, &freed) {
+            error(message);
+            continue;
+        }
+        match lines.entry(sequence.clone()) {
+            Entry::Occupied(entry) => {
+                error(format!(
+                    "\"{}\" is already bound on line {}",
+                    show(&sequence),
+                    entry.get()
+                ));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(line);
+                bindings.sequences.insert(sequence, command);
+            }
+        }
+    }
+    (bindings, errors)
+}
+
+/// Why binding `sequence` to `command` would hide one of `keymap`'s
+/// bindings or an app shortcut without saying so, if it would
+fn hidden(
+    keymap: Keymap,
+    sequence: &[Chord],
+    command: Option<Command>,
+    freed: &HashSet<Chord>,
+) -> Option<String> {
+    let first = sequence[0];
+    if sequence.len() == 1 {
+        // Replacing one of the keymap's bindings is what the config is for,
+        // but the app's shortcuts can only be freed up
+        let title = app_shortcut(first).filter(|_| command.is_some())?;
+        return Some(format!(
+            "\"{first}\" is the app's shortcut for {title}, so it can only be bound to \"none\""
+        ));
+    }
+    if freed.contains(&first) {
+        return None;
+    }
+    let taken = match (
+        keymap.command(first.key_code, first.modifiers),
+        app_shortcut(first),
+    ) {
+        (Some(command), _) => command.name().to_string(),
+        (None, Some(title)) => format!("the app's {title}"),
+        (None, None) => return None,
+    };
+    Some(format!(
+        "\"{}\" would take \"{first}\" away from {taken}; bind \"{first}\" to \"none\" first",
+        show(sequence),
+    ))
+}
+
+/// A sequence of chords as it's written in the config
+fn show(sequence: &[Chord]) -> String {
+    let chords: Vec<String> = sequence.iter().map(Chord::to_string).collect();
+    chords.join(" ")
+}
+
+/// Produce an event whenever the keymap config changes on disk, so it can
+/// be loaded again
+pub fn watch() -> Subscription<()> {
+    struct Watch;
+
+    subscription::channel(
+        std::any::TypeId::of::<Watch>(),
+        1,
+        |mut output| async move {
+            let (sender, mut changes) = mpsc::channel(1);
+            // The directory is watched, as editors often save by replacing the file
+            let watcher = path().and_then(|path| {
+                let mut sender = sender;
+                let mut watcher =
+                    notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                        let changed = event.is_ok_and(|event| {
+                            event
+                                .paths
+                                .iter()
+                                .any(|path| path.file_name().is_some_and(|name| name == FILE_NAME))
+                        });
+                        if changed {
+                            // A change is already waiting to be reported if this fails
+                            let _ = sender.try_send(());
+                        }
+                    })
+                    .ok()?;
+                let dir = path.parent()?;
+                // Made up front, so a config written later is still noticed
+                std::fs::create_dir_all(dir).ok()?;
+                watcher.watch(dir, RecursiveMode::NonRecursive).ok()?;
+                Some(watcher)
+            });
+            loop {
+                match &watcher {
+                    Some(_) => {
+                        changes.next().await;
+                        let _ = output.send(()).await;
+                    }
+                    // Without a directory to watch, there's nothing to report
+                    None => iced::futures::future::pending().await,
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Lookup;
+    use super::*;
+    use crate::backend::Action;
+    use crate::text_editor::emacs;
+
+    fn errors_in(text: &str, keymap: Keymap) -> Vec<(usize, String)> {
+        let (_, errors) = parse(text, keymap);
+        errors
+            .into_iter()
+            .map(|error| (error.line, error.message))
+            .collect()
+    }
+
+    fn errors(text: &str) -> Vec<(usize, String)> {
+        errors_in(text, Keymap::Standard)
+    }
+
+    #[test]
+    fn bindings() {
+        let text = "[bindings]\n\"ctrl+shift+k\" = \"kill_line\"\n\"ctrl+d\" = \"none\"\n";
+        let (bindings, errors) = parse(text, Keymap::Standard);
+        assert_eq!(errors, []);
+        let chord = |text: &str| text.parse::<Chord>().unwrap();
+        assert_eq!(
+            Keymap::Standard.lookup(&bindings, &[chord("ctrl+shift+k")]),
+            Lookup::Command(Command::Emacs(emacs::Command::KillLine))
+        );
+        assert_eq!(
+            Keymap::Standard.lookup(&bindings, &[chord("ctrl+d")]),
+            Lookup::Unbound
+        );
+        assert_eq!(
+            Keymap::Standard.lookup(&bindings, &[chord("ctrl+z")]),
+            Lookup::Command(Command::Action(Action::Undo))
+        );
+    }
+
+    #[test]
+    fn bad_bindings_are_reported_on_their_line() {
+        let text = "[bindings]\n\
+                    \"ctrl+foo\" = \"undo\"\n\
+                    \"ctrl+k\" = \"fly\"\n\
+                    \"\" = \"undo\"\n\
+                    \"alt+k\" = \"undo\"\n\
+                    \"ALT+K\" = \"redo\"\n\
+                    \"alt+k alt+j\" = \"redo\"\n";
+        assert_eq!(
+            errors(text),
+            [
+                (2, "Unknown key \"foo\"".to_string()),
+                (3, "Unknown command \"fly\"".to_string()),
+                (4, "No keys to bind".to_string()),
+                (6, "\"alt+k\" is already bound on line 5".to_string()),
+                (
+                    7,
+                    "\"alt+k alt+j\" conflicts with \"alt+k\" on line 5, as one starts the other"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn syntax_errors_are_reported_on_their_line() {
+        let found = errors("[bindings]\n\"ctrl+k\" = \"undo\"\n\"ctrl+j\" =\n");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, 3);
+        let found = errors("[keys]\n");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, 1);
+    }
+
+    #[test]
+    fn hiding_a_builtin_is_reported() {
+        // Ctrl+X would only start the sequence, so it couldn't cut any more
+        assert_eq!(
+            errors("[bindings]\n\"ctrl+x ctrl+s\" = \"undo\"\n"),
+            [(
+                2,
+                "\"ctrl+x ctrl+s\" would take \"ctrl+x\" away from cut; \
+                 bind \"ctrl+x\" to \"none\" first"
+                    .to_string()
+            )]
+        );
+        // Ctrl+K is only bound in the Emacs keymap
+        assert_eq!(errors("[bindings]\n\"ctrl+k ctrl+k\" = \"undo\"\n"), []);
+        assert_eq!(
+            errors_in("[bindings]\n\"ctrl+k ctrl+k\" = \"undo\"\n", Keymap::Emacs).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn hiding_an_app_shortcut_is_reported() {
+        for keys in ["ctrl+s", "ctrl+w", "ctrl+p"] {
+            let text = format!("[bindings]\n\n\"{keys}\" = \"undo\"\n");
+            let found = errors(&text);
+            assert_eq!(found.len(), 1, "{keys}");
+            assert_eq!(found[0].0, 3);
+            // Taking the binding away only frees it up for the app
+            assert_eq!(errors(&format!("[bindings]\n\"{keys}\" = \"none\"\n")), []);
+        }
+        assert_eq!(
+            errors("[bindings]\n\"ctrl+b ctrl+b\" = \"undo\"\n"),
+            [(
+                2,
+                "\"ctrl+b ctrl+b\" would take \"ctrl+b\" away from the app's Toggle File Tree; \
+                 bind \"ctrl+b\" to \"none\" first"
+                    .to_string()
+            )]
+        );
+    }
+}
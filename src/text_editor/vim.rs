@@ -0,0 +1,1409 @@
+use std::collections::HashMap;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::backend::{Action, Backend, Position};
+
+/// Which keys the modal layer is taking
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Keys are commands
+    #[default]
+    Normal,
+    /// Keys type text
+    Insert,
+    /// Motions extend a selection of characters
+    Visual,
+    /// Motions extend a selection of whole rows
+    VisualLine,
+}
+
+/// A key press, as far as the modal layer is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    /// A letter typed while holding Ctrl
+    Ctrl(char),
+    Escape,
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordStart,
+    WordEnd,
+    WordBack,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    /// `f`, `t`, `F` and `T`, which look for `target` in the row
+    Find {
+        target: char,
+        forwards: bool,
+        till: bool,
+    },
+}
+
+impl Motion {
+    /// Whether an operator given this motion acts on whole rows
+    fn is_linewise(self) -> bool {
+        matches!(
+            self,
+            Motion::Up | Motion::Down | Motion::FirstLine | Motion::LastLine
+        )
+    }
+
+    /// Whether an operator given this motion includes the character it ends on
+    fn is_inclusive(self) -> bool {
+        matches!(
+            self,
+            Motion::WordEnd | Motion::LineEnd | Motion::Find { forwards: true, .. }
+        )
+    }
+}
+
+/// The inner text objects, which leave out surrounding quotes and blanks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextObject {
+    Word,
+    Quote,
+    Paragraph,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Dedent,
+}
+
+/// What an operator acts on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Motion(Motion),
+    Object(TextObject),
+    /// Whole rows, from a doubled operator like `dd`, or the rows of the
+    /// visual selection
+    Lines,
+    /// The visual selection
+    Selection,
+}
+
+/// Where typing starts when entering insert mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InsertAt {
+    Cursor,
+    AfterCursor,
+    LineStart,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Move(Motion),
+    Operate(Operator, Target),
+    Insert(InsertAt),
+    Put {
+        before: bool,
+    },
+    Undo,
+    Redo,
+    /// Select a text object in visual mode
+    Select(TextObject),
+    Visual(Mode),
+    Repeat,
+}
+
+/// A whole command, as typed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Parsed {
+    register: Option<char>,
+    count: Option<usize>,
+    command: Command,
+}
+
+/// The result of parsing the keys typed so far
+enum Parse<T> {
+    Incomplete,
+    Invalid,
+    Complete(T),
+}
+
+impl<T> Parse<T> {
+    fn map<U>(self, f: impl FnOnce(T) -> U) -> Parse<U> {
+        match self {
+            Parse::Incomplete => Parse::Incomplete,
+            Parse::Invalid => Parse::Invalid,
+            Parse::Complete(value) => Parse::Complete(f(value)),
+        }
+    }
+}
+
+/// The largest count, as in Vim. Bigger ones are cut down to it.
+const MAX_COUNT: usize = 999_999_999;
+
+/// The most text a single put makes, so a big count can't exhaust memory
+const MAX_PUT_LENGTH: usize = 1 << 20;
+
+/// A count at the start of `keys`, and the keys after it
+fn parse_count(keys: &[char]) -> (Option<usize>, &[char]) {
+    if !matches!(keys.first(), Some('1'..='9')) {
+        return (None, keys);
+    }
+    let digits = keys.iter().take_while(|key| key.is_ascii_digit()).count();
+    let count = keys[..digits].iter().fold(0usize, |count, digit| {
+        count
+            .saturating_mul(10)
+            .saturating_add(digit.to_digit(10).unwrap_or(0) as usize)
+    });
+    (Some(count.min(MAX_COUNT)), &keys[digits..])
+}
+
+fn parse_motion(keys: &[char]) -> Parse<Motion> {
+    let motion = match keys {
+        [] | ['g'] | ['f' | 't' | 'F' | 'T'] => return Parse::Incomplete,
+        ['h'] => Motion::Left,
+        ['l' | ' '] => Motion::Right,
+        ['k'] => Motion::Up,
+        ['j'] => Motion::Down,
+        ['w'] => Motion::WordStart,
+        ['e'] => Motion::WordEnd,
+        ['b'] => Motion::WordBack,
+        ['0'] => Motion::LineStart,
+        ['^'] => Motion::FirstNonBlank,
+        ['$'] => Motion::LineEnd,
+        ['g', 'g'] => Motion::FirstLine,
+        ['G'] => Motion::LastLine,
+        [find @ ('f' | 't' | 'F' | 'T'), target] => Motion::Find {
+            target: *target,
+            forwards: find.is_lowercase(),
+            till: find.eq_ignore_ascii_case(&'t'),
+        },
+        _ => return Parse::Invalid,
+    };
+    Parse::Complete(motion)
+}
+
+fn parse_object(keys: &[char]) -> Parse<TextObject> {
+    match keys {
+        ['i'] => Parse::Incomplete,
+        ['i', 'w'] => Parse::Complete(TextObject::Word),
+        ['i', '"'] => Parse::Complete(TextObject::Quote),
+        ['i', 'p'] => Parse::Complete(TextObject::Paragraph),
+        _ => Parse::Invalid,
+    }
+}
+
+/// Parse the keys typed in normal or `visual` mode as a command, which is
+/// `["x][count]` followed by a command, or by an operator with an optional
+/// count and a motion or text object
+fn parse(keys: &[char], visual: bool) -> Parse<Parsed> {
+    let (register, keys) = match keys {
+        ['"'] => return Parse::Incomplete,
+        ['"', register, rest @ ..] if register.is_ascii_alphanumeric() || *register == '"' => {
+            (Some(*register), rest)
+        }
+        ['"', ..] => return Parse::Invalid,
+        _ => (None, keys),
+    };
+    let (mut count, keys) = parse_count(keys);
+    let Some((&key, rest)) = keys.split_first() else {
+        return Parse::Incomplete;
+    };
+    let operator = match key {
+        'd' => Some(Operator::Delete),
+        'c' => Some(Operator::Change),
+        'y' => Some(Operator::Yank),
+        '>' => Some(Operator::Indent),
+        '<' => Some(Operator::Dedent),
+        _ => None,
+    };
+    let command = match (operator, visual) {
+        (Some(operator), true) => Command::Operate(operator, Target::Selection),
+        (Some(operator), false) => {
+            let (motion_count, rest) = parse_count(rest);
+            if let Some(motion_count) = motion_count {
+                count = Some(
+                    count
+                        .unwrap_or(1)
+                        .saturating_mul(motion_count)
+                        .min(MAX_COUNT),
+                );
+            }
+            let target = match rest {
+                [] => return Parse::Incomplete,
+                [doubled] if *doubled == key => Parse::Complete(Target::Lines),
+                ['i', ..] => parse_object(rest).map(Target::Object),
+                _ => parse_motion(rest).map(Target::Motion),
+            };
+            match target {
+                Parse::Complete(target) => Command::Operate(operator, target),
+                Parse::Incomplete => return Parse::Incomplete,
+                Parse::Invalid => return Parse::Invalid,
+            }
+        }
+        (None, _) => match (key, visual) {
+            ('i', false) => Command::Insert(InsertAt::Cursor),
+            ('a', false) => Command::Insert(InsertAt::AfterCursor),
+            ('I', false) => Command::Insert(InsertAt::LineStart),
+            ('A', false) => Command::Insert(InsertAt::LineEnd),
+            ('o', false) => Command::Insert(InsertAt::LineBelow),
+            ('O', false) => Command::Insert(InsertAt::LineAbove),
+            ('i', true) => match parse_object(keys) {
+                Parse::Complete(object) => Command::Select(object),
+                Parse::Incomplete => return Parse::Incomplete,
+                Parse::Invalid => return Parse::Invalid,
+            },
+            ('x', true) => Command::Operate(Operator::Delete, Target::Selection),
+            ('x', false) => Command::Operate(Operator::Delete, Target::Motion(Motion::Right)),
+            ('X', false) => Command::Operate(Operator::Delete, Target::Motion(Motion::Left)),
+            ('s', true) => Command::Operate(Operator::Change, Target::Selection),
+            ('s', false) => Command::Operate(Operator::Change, Target::Motion(Motion::Right)),
+            ('D' | 'X', true) => Command::Operate(Operator::Delete, Target::Lines),
+            ('D', false) => Command::Operate(Operator::Delete, Target::Motion(Motion::LineEnd)),
+            ('C' | 'S', true) => Command::Operate(Operator::Change, Target::Lines),
+            ('C', false) => Command::Operate(Operator::Change, Target::Motion(Motion::LineEnd)),
+            ('S', false) => Command::Operate(Operator::Change, Target::Lines),
+            ('Y', _) => Command::Operate(Operator::Yank, Target::Lines),
+            ('p', false) => Command::Put { before: false },
+            ('P', false) => Command::Put { before: true },
+            ('u', false) => Command::Undo,
+            ('\u{12}', false) => Command::Redo,
+            ('.', false) => Command::Repeat,
+            ('v', _) => Command::Visual(Mode::Visual),
+            ('V', _) => Command::Visual(Mode::VisualLine),
+            _ => match parse_motion(keys) {
+                Parse::Complete(motion) => Command::Move(motion),
+                Parse::Incomplete => return Parse::Incomplete,
+                Parse::Invalid => return Parse::Invalid,
+            },
+        },
+    };
+    Parse::Complete(Parsed {
+        register,
+        count,
+        command,
+    })
+}
+
+/// The kinds of character that words are made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Blank,
+    /// Letters, digits and underscores
+    Keyword,
+    Punctuation,
+    /// An empty row, which counts as a word of its own
+    EmptyRow,
+}
+
+fn class_of(grapheme: &str) -> Class {
+    match grapheme.chars().next() {
+        Some(c) if c.is_whitespace() => Class::Blank,
+        Some(c) if c.is_alphanumeric() || c == '_' => Class::Keyword,
+        _ => Class::Punctuation,
+    }
+}
+
+/// Steps through the document a grapheme at a time for the word motions.
+/// The end of each row is a position of its own, which is blank.
+struct Walker<'a> {
+    backend: &'a Backend,
+    row: usize,
+    column: usize,
+    classes: Vec<Class>, // The class of each grapheme in the row
+}
+
+impl<'a> Walker<'a> {
+    fn new(backend: &'a Backend, position: Position) -> Self {
+        let mut walker = Self {
+            backend,
+            row: position.row,
+            column: position.column,
+            classes: Vec::new(),
+        };
+        walker.load_row();
+        walker
+    }
+
+    fn load_row(&mut self) {
+        self.classes = self
+            .backend
+            .line(self.row)
+            .graphemes(true)
+            .map(class_of)
+            .collect();
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            row: self.row,
+            column: self.column,
+        }
+    }
+
+    fn class(&self) -> Class {
+        match self.classes.get(self.column) {
+            Some(class) => *class,
+            None if self.classes.is_empty() => Class::EmptyRow,
+            None => Class::Blank,
+        }
+    }
+
+    /// Step forwards, returning false at the end of the document
+    fn next(&mut self) -> bool {
+        if self.column < self.classes.len() {
+            self.column += 1;
+        } else if self.row + 1 < self.backend.line_count() {
+            self.row += 1;
+            self.column = 0;
+            self.load_row();
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Step backwards, returning false at the start of the document
+    fn previous(&mut self) -> bool {
+        if self.column > 0 {
+            self.column -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.load_row();
+            self.column = self.classes.len();
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Move to the start of the next word
+    fn word_start(&mut self) {
+        let class = self.class();
+        match class {
+            Class::Blank => {}
+            Class::EmptyRow => {
+                if !self.next() {
+                    return;
+                }
+            }
+            _ => {
+                while self.class() == class {
+                    if !self.next() {
+                        return;
+                    }
+                }
+            }
+        }
+        while self.class() == Class::Blank && self.next() {}
+    }
+
+    /// Move to the end of the word, or of the next one if already there
+    fn word_end(&mut self) {
+        if !self.next() {
+            return;
+        }
+        while matches!(self.class(), Class::Blank | Class::EmptyRow) {
+            if !self.next() {
+                return;
+            }
+        }
+        let class = self.class();
+        while self.next() {
+            if self.class() != class {
+                self.previous();
+                break;
+            }
+        }
+    }
+
+    /// Move to the start of the word, or of the previous one if already there
+    fn word_back(&mut self) {
+        if !self.previous() {
+            return;
+        }
+        while self.class() == Class::Blank {
+            if !self.previous() {
+                return;
+            }
+        }
+        let class = self.class();
+        if class == Class::EmptyRow {
+            return;
+        }
+        while self.previous() {
+            if self.class() != class {
+                self.next();
+                break;
+            }
+        }
+    }
+}
+
+/// The text an operator acts on: from `start` up to `end`, or the whole rows
+/// from `start.row` to `end.row` if `linewise`
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    start: Position,
+    end: Position,
+    linewise: bool,
+}
+
+/// The contents of a register
+#[derive(Debug, Clone, Default)]
+struct Register {
+    text: String,
+    // Whether the text is whole rows, which are put on rows of their own
+    linewise: bool,
+}
+
+/// The last change, which `.` repeats
+#[derive(Debug, Clone)]
+struct Change {
+    parsed: Parsed,
+    // The keys typed in insert mode after the command
+    inserted: Vec<Key>,
+}
+
+/// A modal editing layer in the style of Vim, which turns keys into edits
+/// on a backend. It knows nothing about the widget, so it can be driven by
+/// feeding it `Key`s directly.
+#[derive(Debug, Default)]
+pub struct Vim {
+    mode: Mode,
+    // The keys of the command being typed in normal or visual mode
+    pending: Vec<char>,
+    registers: HashMap<char, Register>,
+    last_change: Option<Change>,
+    // Whether keys typed in insert mode are being added to the last change
+    recording: bool,
+    // Where the visual selection started, and the cursor's end of it
+    visual_anchor: Position,
+    visual_head: Position,
+}
+
+impl Vim {
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The keys typed so far of a command that isn't complete
+    pub fn pending(&self) -> String {
+        self.pending.iter().collect()
+    }
+
+    /// Handle `key`, returning whether it was used. Keys that aren't used,
+    /// like the arrows in insert mode, are left to the editor's own bindings.
+    pub fn key(&mut self, backend: &mut Backend, key: Key) -> bool {
+        if self.mode == Mode::Insert {
+            return self.insert_key(backend, key);
+        }
+        let c = match key {
+            Key::Char(c) => c,
+            Key::Enter => 'j',
+            Key::Backspace => 'h',
+            Key::Delete => 'x',
+            Key::Tab => return true,
+            // Ctrl+R is parsed as the control character it types
+            Key::Ctrl('r') => '\u{12}',
+            Key::Ctrl(_) => return false,
+            Key::Escape => {
+                if !self.pending.is_empty() {
+                    self.pending.clear();
+                } else if self.mode == Mode::Normal {
+                    // Drop any extra cursors
+                    backend.action(Action::Escape);
+                } else {
+                    self.mode = Mode::Normal;
+                    backend.set_cursor(self.visual_head, false);
+                    self.settle(backend);
+                }
+                return true;
+            }
+        };
+        self.pending.push(c);
+        let visual = matches!(self.mode, Mode::Visual | Mode::VisualLine);
+        match parse(&self.pending, visual) {
+            Parse::Incomplete => {}
+            Parse::Invalid => self.pending.clear(),
+            Parse::Complete(parsed) => {
+                self.pending.clear();
+                self.execute(backend, parsed);
+            }
+        }
+        true
+    }
+
+    fn insert_key(&mut self, backend: &mut Backend, key: Key) -> bool {
+        match key {
+            Key::Char(c) => backend.action(Action::Insert(c)),
+            Key::Enter => backend.action(Action::Enter),
+            Key::Backspace => backend.action(Action::Backspace),
+            Key::Delete => backend.action(Action::Delete),
+            Key::Tab => backend.action(Action::Indent),
+            Key::Escape => {
+                self.leave_insert(backend);
+                return true;
+            }
+            Key::Ctrl(_) => return false,
+        }
+        if self.recording {
+            if let Some(change) = &mut self.last_change {
+                change.inserted.push(key);
+            }
+        }
+        true
+    }
+
+    fn leave_insert(&mut self, backend: &mut Backend) {
+        self.mode = Mode::Normal;
+        self.recording = false;
+        backend.end_change();
+        // The cursor goes back onto the last character typed
+        let head = backend.selection().head;
+        backend.set_cursor(
+            Position {
+                column: head.column.saturating_sub(1),
+                ..head
+            },
+            false,
+        );
+    }
+
+    fn enter_insert(&mut self, backend: &mut Backend) {
+        self.mode = Mode::Insert;
+        self.recording = true;
+        backend.begin_change();
+    }
+
+    fn execute(&mut self, backend: &mut Backend, parsed: Parsed) {
+        let count = parsed.count.unwrap_or(1);
+        let cursor = match self.mode {
+            Mode::Visual | Mode::VisualLine => self.visual_head,
+            _ => backend.selection().head,
+        };
+        match parsed.command {
+            Command::Move(motion) => {
+                if let Some(target) = motion_target(backend, cursor, motion, parsed.count) {
+                    self.move_to(backend, target);
+                }
+            }
+            Command::Operate(operator, target) => {
+                let Some(region) = self.region(backend, cursor, operator, target, parsed.count)
+                else {
+                    self.leave_visual();
+                    self.settle(backend);
+                    return;
+                };
+                if operator != Operator::Yank {
+                    self.remember(parsed, region);
+                }
+                self.leave_visual();
+                self.operate(backend, operator, region, parsed.register);
+            }
+            Command::Insert(at) => {
+                self.remember(parsed, None);
+                self.enter_insert(backend);
+                let row = cursor.row;
+                let length = backend.line_length(row);
+                match at {
+                    InsertAt::Cursor => {}
+                    InsertAt::AfterCursor => backend.set_cursor(
+                        Position {
+                            column: (cursor.column + 1).min(length),
+                            ..cursor
+                        },
+                        false,
+                    ),
+                    InsertAt::LineStart => backend.set_cursor(first_non_blank(backend, row), false),
+                    InsertAt::LineEnd => {
+                        backend.set_cursor(
+                            Position {
+                                row,
+                                column: length,
+                            },
+                            false,
+                        );
+                    }
+                    InsertAt::LineBelow => {
+                        backend.set_cursor(
+                            Position {
+                                row,
+                                column: length,
+                            },
+                            false,
+                        );
+                        backend.action(Action::Enter);
+                    }
+                    InsertAt::LineAbove => {
+                        backend.set_cursor(Position { row, column: 0 }, false);
+                        backend.action(Action::Enter);
+                        backend.set_cursor(Position { row, column: 0 }, false);
+                    }
+                }
+            }
+            Command::Put { before } => {
+                self.remember(parsed, None);
+                self.put(backend, parsed.register, before, count);
+            }
+            Command::Undo => {
+                for _ in 0..count {
+                    if !backend.can_undo() {
+                        break;
+                    }
+                    backend.action(Action::Undo);
+                }
+                self.settle(backend);
+            }
+            Command::Redo => {
+                for _ in 0..count {
+                    if !backend.can_redo() {
+                        break;
+                    }
+                    backend.action(Action::Redo);
+                }
+                self.settle(backend);
+            }
+            Command::Select(object) => {
+                if let Some(region) = text_object(backend, cursor, object) {
+                    self.visual_anchor = region.start;
+                    self.visual_head = if region.linewise {
+                        region.end
+                    } else {
+                        step_back(backend, region.end)
+                    };
+                    if region.linewise {
+                        self.mode = Mode::VisualLine;
+                    }
+                    self.show_visual(backend);
+                }
+            }
+            Command::Visual(mode) => {
+                if self.mode == mode {
+                    self.mode = Mode::Normal;
+                    backend.set_cursor(self.visual_head, false);
+                    self.settle(backend);
+                } else {
+                    if self.mode == Mode::Normal {
+                        self.visual_anchor = cursor;
+                        self.visual_head = cursor;
+                    }
+                    self.mode = mode;
+                    self.show_visual(backend);
+                }
+            }
+            Command::Repeat => {
+                let Some(change) = self.last_change.clone() else {
+                    return;
+                };
+                let parsed = Parsed {
+                    count: parsed.count.or(change.parsed.count),
+                    ..change.parsed
+                };
+                self.execute(backend, parsed);
+                if self.mode == Mode::Insert {
+                    for key in change.inserted {
+                        self.insert_key(backend, key);
+                    }
+                    self.leave_insert(backend);
+                }
+            }
+        }
+    }
+
+    /// Record a change for `.` to repeat. A visual selection is repeated as
+    /// the same number of rows, or characters if it was within one row.
+    fn remember(&mut self, parsed: Parsed, region: impl Into<Option<Region>>) {
+        let parsed = match (parsed.command, region.into()) {
+            (Command::Operate(operator, Target::Selection | Target::Lines), Some(region))
+                if self.mode != Mode::Normal =>
+            {
+                let (count, target) = if region.linewise {
+                    (region.end.row - region.start.row + 1, Target::Lines)
+                } else if region.start.row == region.end.row {
+                    (
+                        region.end.column - region.start.column,
+                        Target::Motion(Motion::Right),
+                    )
+                } else {
+                    self.last_change = None;
+                    return;
+                };
+                Parsed {
+                    count: Some(count),
+                    command: Command::Operate(operator, target),
+                    ..parsed
+                }
+            }
+            _ => parsed,
+        };
+        self.last_change = Some(Change {
+            parsed,
+            inserted: Vec::new(),
+        });
+    }
+
+    fn leave_visual(&mut self) {
+        if matches!(self.mode, Mode::Visual | Mode::VisualLine) {
+            self.mode = Mode::Normal;
+        }
+    }
+
+    fn move_to(&mut self, backend: &mut Backend, target: Position) {
+        if self.mode == Mode::Normal {
+            backend.set_cursor(target, false);
+            self.settle(backend);
+        } else {
+            self.visual_head = target;
+            self.show_visual(backend);
+        }
+    }
+
+    /// Select what the visual selection covers in the backend. The character
+    /// under the cursor is covered by the block cursor rather than selected.
+    fn show_visual(&self, backend: &mut Backend) {
+        let (anchor, head) = (self.visual_anchor, self.visual_head);
+        if self.mode == Mode::VisualLine {
+            let (first, last) = (anchor.row.min(head.row), anchor.row.max(head.row));
+            let start = Position {
+                row: first,
+                column: 0,
+            };
+            let end = Position {
+                row: last,
+                column: backend.line_length(last),
+            };
+            if head.row < anchor.row {
+                backend.set_cursor(end, false);
+                backend.set_cursor(start, true);
+            } else {
+                backend.set_cursor(start, false);
+                backend.set_cursor(end, true);
+            }
+        } else if head < anchor {
+            backend.set_cursor(step(backend, anchor), false);
+            backend.set_cursor(head, true);
+        } else {
+            backend.set_cursor(anchor, false);
+            backend.set_cursor(head, true);
+        }
+    }
+
+    /// Leave the cursor on a character in normal mode, rather than past
+    /// the end of the row or around a selection
+    fn settle(&self, backend: &mut Backend) {
+        if self.mode != Mode::Normal {
+            return;
+        }
+        let head = backend.selection().head;
+        let length = backend.line_length(head.row);
+        let column = head.column.min(length.saturating_sub(1));
+        backend.set_cursor(Position { column, ..head }, false);
+    }
+
+    /// What `operator` acts on when given `target` from `cursor`
+    fn region(
+        &self,
+        backend: &Backend,
+        cursor: Position,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+    ) -> Option<Region> {
+        let region = match target {
+            Target::Selection | Target::Lines if self.mode != Mode::Normal => {
+                let (anchor, head) = (self.visual_anchor, self.visual_head);
+                let (start, end) = (anchor.min(head), anchor.max(head));
+                Region {
+                    start,
+                    end: if target == Target::Lines || self.mode == Mode::VisualLine {
+                        end
+                    } else {
+                        step(backend, end)
+                    },
+                    linewise: target == Target::Lines || self.mode == Mode::VisualLine,
+                }
+            }
+            Target::Selection => return None,
+            Target::Lines => Region {
+                start: cursor,
+                end: Position {
+                    row: cursor
+                        .row
+                        .saturating_add(count.unwrap_or(1).saturating_sub(1))
+                        .min(backend.line_count() - 1),
+                    column: 0,
+                },
+                linewise: true,
+            },
+            Target::Object(object) => text_object(backend, cursor, object)?,
+            Target::Motion(motion) => {
+                // `cw` on a word changes up to its end, like `ce`
+                let on_word = !matches!(
+                    Walker::new(backend, cursor).class(),
+                    Class::Blank | Class::EmptyRow
+                );
+                let motion =
+                    if operator == Operator::Change && motion == Motion::WordStart && on_word {
+                        Motion::WordEnd
+                    } else {
+                        motion
+                    };
+                let mut target = motion_target(backend, cursor, motion, count)?;
+                // A word motion doesn't take an operator past the end of the row
+                if motion == Motion::WordStart && target.row > cursor.row {
+                    let row = target.row - 1;
+                    target = Position {
+                        row,
+                        column: backend.line_length(row),
+                    };
+                }
+                let (start, end) = (cursor.min(target), cursor.max(target));
+                Region {
+                    start,
+                    end: if motion.is_inclusive() {
+                        step_in_row(backend, end)
+                    } else {
+                        end
+                    },
+                    linewise: motion.is_linewise(),
+                }
+            }
+        };
+        if !region.linewise && region.start == region.end {
+            return None;
+        }
+        Some(region)
+    }
+
+    fn operate(
+        &mut self,
+        backend: &mut Backend,
+        operator: Operator,
+        region: Region,
+        register: Option<char>,
+    ) {
+        let (first_row, last_row) = (region.start.row, region.end.row);
+        let text = if region.linewise {
+            let end = Position {
+                row: last_row,
+                column: backend.line_length(last_row),
+            };
+            let mut text = backend.text_between(row_start(first_row), end);
+            text.push('\n');
+            text
+        } else {
+            backend.text_between(region.start, region.end)
+        };
+        match operator {
+            Operator::Yank => {
+                self.store(register, text, region.linewise, true);
+                let cursor = backend.selection().head.min(region.start);
+                backend.set_cursor(cursor, false);
+                self.settle(backend);
+            }
+            Operator::Delete => {
+                self.store(register, text, region.linewise, false);
+                if region.linewise {
+                    let (start, end) = line_span(backend, first_row, last_row);
+                    select(backend, start, end);
+                    backend.action(Action::Delete);
+                    let row = first_row.min(backend.line_count() - 1);
+                    backend.set_cursor(first_non_blank(backend, row), false);
+                } else {
+                    select(backend, region.start, region.end);
+                    backend.action(Action::Delete);
+                }
+                self.settle(backend);
+            }
+            Operator::Change => {
+                self.store(register, text, region.linewise, false);
+                self.enter_insert(backend);
+                if region.linewise {
+                    let end = Position {
+                        row: last_row,
+                        column: backend.line_length(last_row),
+                    };
+                    select(backend, row_start(first_row), end);
+                } else {
+                    select(backend, region.start, region.end);
+                }
+                if !backend.selection().is_empty() {
+                    backend.action(Action::Delete);
+                }
+            }
+            Operator::Indent | Operator::Dedent => {
+                // A selection within a row would be replaced by the indent,
+                // while the cursor alone indents its own row
+                if first_row == last_row {
+                    backend.set_cursor(row_start(first_row), false);
+                } else {
+                    let end = Position {
+                        row: last_row,
+                        column: backend.line_length(last_row),
+                    };
+                    select(backend, row_start(first_row), end);
+                }
+                match operator {
+                    Operator::Indent if backend.line_length(first_row) == 0 => {}
+                    Operator::Indent => backend.action(Action::Indent),
+                    _ => backend.action(Action::Dedent),
+                }
+                backend.set_cursor(first_non_blank(backend, first_row), false);
+                self.settle(backend);
+            }
+        }
+    }
+
+    /// Put text into a register. Everything goes into the unnamed register,
+    /// and yanks also go into `0` unless another register was named.
+    /// Naming a register in capitals appends to it.
+    fn store(&mut self, register: Option<char>, text: String, linewise: bool, yank: bool) {
+        let value = Register { text, linewise };
+        match register {
+            Some(name) if name.is_ascii_uppercase() => {
+                let register = self.registers.entry(name.to_ascii_lowercase()).or_default();
+                register.text.push_str(&value.text);
+                register.linewise |= value.linewise;
+            }
+            Some(name) if name != '"' => {
+                self.registers.insert(name, value.clone());
+            }
+            _ if yank => {
+                self.registers.insert('0', value.clone());
+            }
+            _ => {}
+        }
+        self.registers.insert('"', value);
+    }
+
+    fn put(&mut self, backend: &mut Backend, register: Option<char>, before: bool, count: usize) {
+        let name = register.map_or('"', |name| name.to_ascii_lowercase());
+        let Some(register) = self.registers.get(&name).cloned() else {
+            return;
+        };
+        let count = count
+            .min(MAX_PUT_LENGTH / register.text.len().max(1))
+            .max(1);
+        let text = register.text.repeat(count);
+        let head = backend.selection().head;
+        if register.linewise {
+            let row = if before { head.row } else { head.row + 1 };
+            if row < backend.line_count() {
+                backend.set_cursor(row_start(row), false);
+                backend.insert(&text);
+            } else {
+                // There's no line break after the last row to put the rows after
+                let last = backend.line_count() - 1;
+                backend.set_cursor(
+                    Position {
+                        row: last,
+                        column: backend.line_length(last),
+                    },
+                    false,
+                );
+                backend.insert(&format!("\n{}", text.strip_suffix('\n').unwrap_or(&text)));
+            }
+            backend.set_cursor(first_non_blank(backend, row), false);
+        } else {
+            if !before {
+                backend.set_cursor(step_in_row(backend, head), false);
+            }
+            backend.insert(&text);
+            // The cursor ends up on the last character put
+            let end = backend.selection().head;
+            backend.set_cursor(step_back(backend, end), false);
+        }
+        self.settle(backend);
+    }
+}
+
+fn row_start(row: usize) -> Position {
+    Position { row, column: 0 }
+}
+
+fn first_non_blank(backend: &Backend, row: usize) -> Position {
+    let column = backend
+        .line(row)
+        .graphemes(true)
+        .take_while(|grapheme| grapheme.chars().all(char::is_whitespace))
+        .count();
+    Position { row, column }
+}
+
+/// Select from `start` up to `end`, leaving a single cursor
+fn select(backend: &mut Backend, start: Position, end: Position) {
+    backend.set_cursor(start, false);
+    backend.set_cursor(end, true);
+}
+
+/// The position one grapheme after `position`, which is the start of the
+/// next row at the end of a row
+fn step(backend: &Backend, position: Position) -> Position {
+    let mut walker = Walker::new(backend, position);
+    walker.next();
+    walker.position()
+}
+
+/// The position one grapheme before `position`
+fn step_back(backend: &Backend, position: Position) -> Position {
+    let mut walker = Walker::new(backend, position);
+    walker.previous();
+    walker.position()
+}
+
+/// The position one grapheme after `position`, but not past the end of its row
+fn step_in_row(backend: &Backend, position: Position) -> Position {
+    Position {
+        column: (position.column + 1).min(backend.line_length(position.row)),
+        ..position
+    }
+}
+
+/// The chars covering the whole rows from `first` to `last`, along with a
+/// line break so no empty row is left behind
+fn line_span(backend: &Backend, first: usize, last: usize) -> (Position, Position) {
+    if last + 1 < backend.line_count() {
+        (row_start(first), row_start(last + 1))
+    } else if first > 0 {
+        // The last row has no line break of its own, so take the one before it
+        (
+            Position {
+                row: first - 1,
+                column: backend.line_length(first - 1),
+            },
+            Position {
+                row: last,
+                column: backend.line_length(last),
+            },
+        )
+    } else {
+        (
+            row_start(first),
+            Position {
+                row: last,
+                column: backend.line_length(last),
+            },
+        )
+    }
+}
+
+/// Where `motion` takes the cursor from `from`, or `None` if it can't move
+/// there. A `count` repeats the motion, except for `gg` and `G`, where it's
+/// the row number to go to.
+fn motion_target(
+    backend: &Backend,
+    from: Position,
+    motion: Motion,
+    count: Option<usize>,
+) -> Option<Position> {
+    let times = count.unwrap_or(1).max(1);
+    let last_row = backend.line_count() - 1;
+    let column_in = |row: usize| from.column.min(backend.line_length(row));
+    let target = match motion {
+        Motion::Left => Position {
+            column: from.column.saturating_sub(times),
+            ..from
+        },
+        Motion::Right => Position {
+            column: from
+                .column
+                .saturating_add(times)
+                .min(backend.line_length(from.row)),
+            ..from
+        },
+        Motion::Up => {
+            let row = from.row.saturating_sub(times);
+            Position {
+                row,
+                column: column_in(row),
+            }
+        }
+        Motion::Down => {
+            let row = from.row.saturating_add(times).min(last_row);
+            Position {
+                row,
+                column: column_in(row),
+            }
+        }
+        Motion::WordStart | Motion::WordEnd | Motion::WordBack => {
+            let mut walker = Walker::new(backend, from);
+            for _ in 0..times {
+                let before = walker.position();
+                match motion {
+                    Motion::WordStart => walker.word_start(),
+                    Motion::WordEnd => walker.word_end(),
+                    _ => walker.word_back(),
+                }
+                // At either end of the document, the rest would do nothing
+                if walker.position() == before {
+                    break;
+                }
+            }
+            walker.position()
+        }
+        Motion::LineStart => row_start(from.row),
+        Motion::FirstNonBlank => first_non_blank(backend, from.row),
+        Motion::LineEnd => {
+            let row = from.row.saturating_add(times - 1).min(last_row);
+            Position {
+                row,
+                column: backend.line_length(row).saturating_sub(1),
+            }
+        }
+        Motion::FirstLine => first_non_blank(backend, count.unwrap_or(1).min(last_row + 1) - 1),
+        Motion::LastLine => {
+            let row = count.map_or(last_row, |count| count.min(last_row + 1) - 1);
+            first_non_blank(backend, row)
+        }
+        Motion::Find {
+            target,
+            forwards,
+            till,
+        } => {
+            let line = backend.line(from.row);
+            let graphemes: Vec<&str> = line.graphemes(true).collect();
+            let is_target = |column: &usize| graphemes[*column].starts_with(target);
+            // `t` and `T` stop next to the character, so they skip over one
+            // right next to the cursor to make repeating them useful
+            let skip = usize::from(till && times > 0);
+            let column = if forwards {
+                (from.column + 1 + skip..graphemes.len())
+                    .filter(is_target)
+                    .nth(times - 1)?
+            } else {
+                (0..from.column.saturating_sub(skip))
+                    .rev()
+                    .filter(is_target)
+                    .nth(times - 1)?
+            };
+            let column = match (till, forwards) {
+                (false, _) => column,
+                (true, true) => column - 1,
+                (true, false) => column + 1,
+            };
+            Position {
+                row: from.row,
+                column,
+            }
+        }
+    };
+    Some(target)
+}
+
+/// The region of a text object around `cursor`
+fn text_object(backend: &Backend, cursor: Position, object: TextObject) -> Option<Region> {
+    let row = cursor.row;
+    let line = backend.line(row);
+    let graphemes: Vec<&str> = line.graphemes(true).collect();
+    let charwise = |start: usize, end: usize| Region {
+        start: Position { row, column: start },
+        end: Position { row, column: end },
+        linewise: false,
+    };
+    match object {
+        TextObject::Word => {
+            if graphemes.is_empty() {
+                return None;
+            }
+            let column = cursor.column.min(graphemes.len() - 1);
+            let class = class_of(graphemes[column]);
+            let start = graphemes[..column]
+                .iter()
+                .rposition(|grapheme| class_of(grapheme) != class)
+                .map_or(0, |i| i + 1);
+            let end = graphemes[column..]
+                .iter()
+                .position(|grapheme| class_of(grapheme) != class)
+                .map_or(graphemes.len(), |i| column + i);
+            Some(charwise(start, end))
+        }
+        TextObject::Quote => {
+            // Quotes pair up from the start of the row, skipping escaped ones
+            let mut quotes = Vec::new();
+            let mut escaped = false;
+            for (column, grapheme) in graphemes.iter().enumerate() {
+                match *grapheme {
+                    "\"" if !escaped => quotes.push(column),
+                    "\\" => {
+                        escaped = !escaped;
+                        continue;
+                    }
+                    _ => {}
+                }
+                escaped = false;
+            }
+            let (open, close) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|(_, close)| *close >= cursor.column)?;
+            Some(charwise(open + 1, close))
+        }
+        TextObject::Paragraph => {
+            // A paragraph is a run of rows that are all blank or all not
+            let is_blank = |row: usize| backend.line(row).trim().is_empty();
+            let blank = is_blank(row);
+            let first = (0..row)
+                .rev()
+                .find(|row| is_blank(*row) != blank)
+                .map_or(0, |row| row + 1);
+            let last = (row + 1..backend.line_count())
+                .find(|row| is_blank(*row) != blank)
+                .map_or(backend.line_count() - 1, |row| row - 1);
+            Some(Region {
+                start: row_start(first),
+                end: row_start(last),
+                linewise: true,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Type `keys` into a new `Vim` over `text`, with `\x1b` as Escape
+    fn typed(text: &str, keys: &str) -> (Backend, Vim) {
+        let mut backend = Backend::from(text);
+        let mut vim = Vim::default();
+        for c in keys.chars() {
+            let key = match c {
+                '\x1b' => Key::Escape,
+                '\n' => Key::Enter,
+                c => Key::Char(c),
+            };
+            vim.key(&mut backend, key);
+        }
+        (backend, vim)
+    }
+
+    fn after(text: &str, keys: &str) -> String {
+        typed(text, keys).0.content()
+    }
+
+    fn cursor(text: &str, keys: &str) -> (usize, usize) {
+        let head = typed(text, keys).0.selection().head;
+        (head.row, head.column)
+    }
+
+    #[test]
+    fn motions() {
+        assert_eq!(cursor("one two three", "w"), (0, 4));
+        assert_eq!(cursor("one two three", "2w"), (0, 8));
+        assert_eq!(cursor("one two three", "e"), (0, 2));
+        assert_eq!(cursor("one two three", "$b"), (0, 8));
+        assert_eq!(cursor("  indented", "$^"), (0, 2));
+        assert_eq!(cursor("a\nb\nc", "G"), (2, 0));
+        assert_eq!(cursor("a\nb\nc", "Ggg"), (0, 0));
+        assert_eq!(cursor("a,b,c", "2f,"), (0, 3));
+        assert_eq!(cursor("ab,c", "t,"), (0, 1));
+        assert_eq!(cursor("abc", "$0"), (0, 0));
+        // The cursor never goes past the last character in normal mode
+        assert_eq!(cursor("abc", "10l"), (0, 2));
+    }
+
+    #[test]
+    fn operators_with_motions() {
+        assert_eq!(after("one two three", "dw"), "two three");
+        assert_eq!(after("one two three", "de"), " two three");
+        assert_eq!(after("one two three", "wd$"), "one ");
+        assert_eq!(after("one two three", "$db"), "one two e");
+        assert_eq!(after("a,b,c", "df,"), "b,c");
+        assert_eq!(after("ab,c", "dt,"), ",c");
+        assert_eq!(after("one\ntwo\nthree", "dj"), "three");
+        assert_eq!(after("one\ntwo\nthree", "jdk"), "three");
+        assert_eq!(after("one two", "cwnew\x1b"), "new two");
+        assert_eq!(after("one\ntwo", ">j"), "    one\n    two");
+        assert_eq!(after("    one", "<<"), "one");
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(after("one\ntwo\nthree", "dd"), "two\nthree");
+        assert_eq!(after("one\ntwo\nthree", "Gdd"), "one\ntwo");
+        assert_eq!(after("one\ntwo\nthree", "ccnew\x1b"), "new\ntwo\nthree");
+        assert_eq!(after("one\ntwo", "yyp"), "one\none\ntwo");
+        assert_eq!(after("one\ntwo", "jyyP"), "one\ntwo\ntwo");
+    }
+
+    #[test]
+    fn text_objects() {
+        assert_eq!(after("one two three", "wdiw"), "one  three");
+        assert_eq!(after("say \"hello\" now", "fhdi\""), "say \"\" now");
+        assert_eq!(
+            after("say \"hello\" now", "fhci\"bye\x1b"),
+            "say \"bye\" now"
+        );
+        assert_eq!(after("one\ntwo\n\nthree", "dip"), "\nthree");
+        assert_eq!(after("one two three", "wviwd"), "one  three");
+    }
+
+    #[test]
+    fn counts() {
+        assert_eq!(after("a b c d", "2dw"), "c d");
+        assert_eq!(after("a b c d", "d2w"), "c d");
+        assert_eq!(after("a b c d e f g", "2d2w"), "e f g");
+        assert_eq!(after("abcdef", "3x"), "def");
+        assert_eq!(after("1\n2\n3\n4", "2dd"), "3\n4");
+        assert_eq!(after("ab", "yl3p"), "aaaab");
+        assert_eq!(after("a b c", "xxx2u"), " b c");
+    }
+
+    #[test]
+    fn huge_counts() {
+        let count = "99999999999999999999";
+        assert_eq!(cursor("abc\ndef", &format!("{count}l")), (0, 2));
+        assert_eq!(cursor("abc\ndef", &format!("{count}j")), (1, 0));
+        assert_eq!(cursor("abc\ndef", &format!("{count}$")), (1, 2));
+        assert_eq!(cursor("a b c", &format!("{count}w")), (0, 4));
+        assert_eq!(after("abc\ndef", &format!("{count}dd")), "");
+        assert_eq!(after("abc", &format!("x{count}u")), "abc");
+        assert_eq!(after("abc", &format!("xu{count}\u{12}")), "bc");
+        assert!(after("abc", &format!("yl{count}p")).len() <= MAX_PUT_LENGTH + 3);
+    }
+
+    #[test]
+    fn registers() {
+        assert_eq!(after("one two", "\"ayw\"byewP\"ap"), "one oneone two");
+        assert_eq!(after("one\ntwo", "\"add\"ap"), "two\none");
+        // Deleting without a register doesn't touch the named ones
+        assert_eq!(after("one two", "\"ayiwwdiw\"ap"), "one one");
+    }
+
+    #[test]
+    fn repeat() {
+        assert_eq!(after("a b c d", "dw."), "c d");
+        assert_eq!(after("a b c d e", "dw3."), "e");
+        assert_eq!(after("one\ntwo", "A!\x1bj."), "one!\ntwo!");
+        assert_eq!(after("x\ny", "ciwz\x1bj."), "z\nz");
+        assert_eq!(after("abcdef", "vlld."), "");
+    }
+
+    #[test]
+    fn modes() {
+        let (_, vim) = typed("abc", "i");
+        assert_eq!(vim.mode(), Mode::Insert);
+        let (_, vim) = typed("abc", "v");
+        assert_eq!(vim.mode(), Mode::Visual);
+        let (_, vim) = typed("abc", "V\x1b");
+        assert_eq!(vim.mode(), Mode::Normal);
+        let (_, vim) = typed("abc", "2d");
+        assert_eq!(vim.pending(), "2d");
+        assert_eq!(after("one\ntwo\nthree", "Vjd"), "three");
+    }
+}
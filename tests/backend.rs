@@ -0,0 +1,236 @@
+use proptest::prelude::*;
+use proptest::sample::select;
+use text_editor::backend::{Action, Backend, SearchQuery, ViewId};
+
+/// Pieces of text that are easy to split in the middle of: combining
+/// marks, an emoji joined with ZWJs, CRLF line breaks and tabs
+const PIECES: &[&str] = &[
+    "a",
+    "Z",
+    "_",
+    " ",
+    "\t",
+    "(",
+    "}",
+    "e\u{301}",
+    "\u{301}",
+    "👩\u{200d}👩\u{200d}👧",
+    "\u{200d}",
+    "\n",
+    "\r\n",
+    "\r",
+];
+
+const ACTIONS: &[Action] = &[
+    Action::Delete,
+    Action::Up,
+    Action::Down,
+    Action::Left,
+    Action::Right,
+    Action::Home,
+    Action::End,
+    Action::PageUp,
+    Action::PageDown,
+    Action::Escape,
+    Action::Enter,
+    Action::Backspace,
+    Action::Undo,
+    Action::Redo,
+    Action::SelectUp,
+    Action::SelectDown,
+    Action::SelectLeft,
+    Action::SelectRight,
+    Action::SelectHome,
+    Action::SelectEnd,
+    Action::SelectPageUp,
+    Action::SelectPageDown,
+    Action::SelectAll,
+    Action::DocumentStart,
+    Action::DocumentEnd,
+    Action::SelectDocumentStart,
+    Action::SelectDocumentEnd,
+    Action::Indent,
+    Action::Dedent,
+    Action::ExpandSelection,
+    Action::ShrinkSelection,
+    Action::MatchingBracket,
+    Action::FindNext,
+    Action::FindPrevious,
+    Action::AddCursorUp,
+    Action::AddCursorDown,
+    Action::AddNextOccurrence,
+    Action::WordLeft,
+    Action::WordRight,
+    Action::SelectWordLeft,
+    Action::SelectWordRight,
+    Action::SubwordLeft,
+    Action::SubwordRight,
+    Action::SelectSubwordLeft,
+    Action::SelectSubwordRight,
+    Action::DeleteWordBackward,
+    Action::DeleteWordForward,
+    Action::DeleteSubwordBackward,
+    Action::DeleteSubwordForward,
+];
+
+/// Plain text and regexes, the last of which can match across lines
+const QUERIES: &[(&str, bool)] = &[
+    ("a", false),
+    ("a a", false),
+    ("e\u{301}", false),
+    (r"\w+", true),
+    (r"^[a_]|Z$", true),
+    (r"a\s*Z", true),
+];
+
+fn text() -> impl Strategy<Value = String> {
+    prop::collection::vec(select(PIECES), 0..40).prop_map(|pieces| pieces.concat())
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    let typed = PIECES
+        .iter()
+        .flat_map(|piece| piece.chars())
+        .collect::<Vec<_>>();
+    prop_oneof![select(ACTIONS), select(typed).prop_map(Action::Insert)]
+}
+
+fn query() -> impl Strategy<Value = SearchQuery> {
+    (select(QUERIES), any::<bool>(), any::<bool>()).prop_map(
+        |((text, regex), case_sensitive, whole_word)| SearchQuery {
+            text: text.to_string(),
+            regex,
+            case_sensitive,
+            whole_word,
+        },
+    )
+}
+
+/// Every cursor is within the document, on a grapheme boundary
+fn assert_cursors_valid(backend: &Backend) {
+    for selection in backend.selections() {
+        for position in [selection.anchor, selection.head] {
+            assert!(
+                position.row < backend.line_count(),
+                "{position:?} is past the last row"
+            );
+            assert!(
+                position.column <= backend.line_length(position.row),
+                "{position:?} is past the end of its row",
+            );
+        }
+        // Selections are read for copying, which must not panic
+        backend.text_between(selection.start(), selection.end());
+    }
+}
+
+proptest! {
+    #[test]
+    fn actions_keep_cursors_valid(
+        text in text(),
+        actions in prop::collection::vec(action(), 0..60),
+    ) {
+        let mut backend = Backend::from(text.as_str());
+        backend.set_page_rows(3);
+        for action in actions {
+            backend.action(action);
+            assert_cursors_valid(&backend);
+        }
+    }
+
+    #[test]
+    fn clipboard_keeps_cursors_valid(
+        text in text(),
+        pasted in text(),
+        actions in prop::collection::vec(action(), 0..30),
+    ) {
+        let mut backend = Backend::from(text.as_str());
+        for action in actions {
+            backend.action(action);
+            let copied = backend.cut();
+            assert_cursors_valid(&backend);
+            backend.paste(&pasted);
+            assert_cursors_valid(&backend);
+            backend.paste(&copied);
+            assert_cursors_valid(&backend);
+        }
+    }
+
+    #[test]
+    fn search_matches_follow_edits(
+        text in text(),
+        query in query(),
+        actions in prop::collection::vec(action(), 0..40),
+    ) {
+        let mut backend = Backend::from(text.as_str());
+        backend.set_search(&query).unwrap();
+        for action in actions {
+            backend.action(action);
+            // The same as searching the edited text from scratch
+            let mut searched = Backend::from(backend.content().as_str());
+            searched.set_search(&query).unwrap();
+            let rows = 0..backend.line_count();
+            assert_eq!(
+                backend.search_matches(rows.clone()),
+                searched.search_matches(rows)
+            );
+        }
+    }
+}
+
+#[test]
+fn views_type_undo_steps_of_their_own() {
+    let mut backend = Backend::from("");
+    backend.action(Action::Insert('a'));
+    backend.set_view(ViewId(1));
+    backend.action(Action::DocumentEnd);
+    backend.action(Action::Insert('b'));
+    backend.set_view(ViewId(0));
+    backend.action(Action::Insert('c'));
+    assert_eq!(backend.content(), "acb");
+    // Typing after the other view's edit doesn't join its undo step
+    backend.action(Action::Undo);
+    assert_eq!(backend.content(), "ab");
+    backend.set_view(ViewId(1));
+    backend.action(Action::Undo);
+    assert_eq!(backend.content(), "a");
+}
+
+#[test]
+fn undoing_back_to_the_saved_text_is_clean() {
+    let mut backend = Backend::from("");
+    backend.action(Action::Insert('a'));
+    assert!(backend.is_dirty());
+    backend.action(Action::Undo);
+    assert!(!backend.is_dirty());
+    backend.action(Action::Redo);
+    assert!(backend.is_dirty());
+
+    let path = std::env::temp_dir().join(format!("text_editor_saved_{}", std::process::id()));
+    let mut backend = Backend::from("");
+    backend.action(Action::Insert('a'));
+    backend.save_as(&path).unwrap();
+    assert!(!backend.is_dirty());
+    // Typing right after saving doesn't join the saved undo step
+    backend.action(Action::Insert('b'));
+    assert!(backend.is_dirty());
+    backend.action(Action::Undo);
+    assert_eq!(backend.content(), "a");
+    assert!(!backend.is_dirty());
+    backend.action(Action::Undo);
+    assert!(backend.is_dirty());
+    // A new edit replaces the saved state's redo step for good
+    backend.action(Action::Insert('a'));
+    assert!(backend.is_dirty());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn failed_save_as_keeps_the_path() {
+    let mut backend = Backend::from("");
+    backend.action(Action::Insert('a'));
+    let missing = std::env::temp_dir().join(format!("text_editor_missing_{}", std::process::id()));
+    assert!(backend.save_as(missing.join("file.rs")).is_err());
+    assert_eq!(backend.path(), None);
+    assert!(backend.is_dirty());
+}